@@ -0,0 +1,96 @@
+//! Benchmarks for each phase of `physics_update` at increasing particle counts. Filter to run a
+//! subset, e.g. `cargo bench -- density`.
+
+use cgmath::Vector2;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use fluid::cursor_tool::CursorState;
+use fluid::physics::{
+    apply_force_fields, apply_repulsive_particle_force, bound_particles, compute_densities, integrate_particles,
+};
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::{Fp, PARTICLE_COUNT, WORLD_HEIGHT, WORLD_WIDTH};
+
+const PARTICLE_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+const DELTA_TIME: Fp = 0.005;
+
+/// Particles per square metre in the default scenes, which fill about half the world
+const PARTICLE_DENSITY: Fp = PARTICLE_COUNT as Fp / (WORLD_WIDTH * WORLD_HEIGHT / 2.0);
+
+/// A square lattice at the density of the default scenes, so particles have a typical number of
+/// neighbours at every count, with the neighbour grid and densities built. The larger tanks
+/// spread past the world, so the tank is sized to the block and its walls bound it instead.
+fn tank(particle_count: usize) -> SceneData {
+    let side = (particle_count as Fp / PARTICLE_DENSITY).sqrt();
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(side, side),
+        },
+        particle_count,
+        1,
+    )
+    .with_size(Vector2::new(side, side));
+    scene_data.rebuild_neighbour_grid();
+    compute_densities(&mut scene_data);
+    scene_data
+}
+
+/// A copy of `tank`, which can't be cloned directly as scenes hold boxed force fields
+fn copy_tank(tank: &SceneData) -> SceneData {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1).with_size(tank.size);
+    scene_data.particles = tank.particles.clone();
+    scene_data.rebuild_neighbour_grid();
+    scene_data
+}
+
+/// Benchmarks `phase` on a tank of each size. Every run gets a fresh copy of the tank, so phases
+/// that move particles or accumulate accelerations measure the same work every time.
+fn bench_phase(c: &mut Criterion, name: &str, mut phase: impl FnMut(&mut SceneData)) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+    for particle_count in PARTICLE_COUNTS {
+        let tank = tank(particle_count);
+        group.bench_function(BenchmarkId::from_parameter(particle_count), |b| {
+            b.iter_batched_ref(
+                || copy_tank(&tank),
+                |scene_data| phase(scene_data),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn neighbour_search(c: &mut Criterion) {
+    bench_phase(c, "neighbour_grid_rebuild", SceneData::rebuild_neighbour_grid);
+    bench_phase(c, "neighbour_query", |scene_data| {
+        let grid = &scene_data.neighbour_grid;
+        let mut candidates = 0;
+        for particle in &scene_data.particles {
+            grid.for_each_candidate(particle.pos, |_| candidates += 1);
+        }
+        criterion::black_box(candidates);
+    });
+}
+
+fn densities(c: &mut Criterion) {
+    bench_phase(c, "compute_densities", compute_densities);
+}
+
+fn forces(c: &mut Criterion) {
+    bench_phase(c, "apply_force_fields", |scene_data| {
+        apply_force_fields(scene_data, &CursorState::None)
+    });
+    bench_phase(c, "apply_repulsive_particle_force", apply_repulsive_particle_force);
+}
+
+fn integration(c: &mut Criterion) {
+    bench_phase(c, "integration", |scene_data| integrate_particles(scene_data, DELTA_TIME));
+}
+
+fn boundaries(c: &mut Criterion) {
+    bench_phase(c, "bound_particles", |scene_data| bound_particles(scene_data, DELTA_TIME));
+}
+
+criterion_group!(benches, neighbour_search, densities, forces, integration, boundaries);
+criterion_main!(benches);
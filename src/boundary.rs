@@ -0,0 +1,188 @@
+use crate::particle::Particles;
+use crate::physics::COEF_OF_REST;
+use crate::scene_data::SceneData;
+use crate::Fp;
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2, Zero};
+use serde::{Deserialize, Serialize};
+
+const BOUNDARY_THICKNESS: Fp = 0.01;
+
+/// Position and velocity of something moving kinematically at a point in time
+pub struct MotionState {
+    pub offset: Vector2<Fp>,
+    pub angle: Fp,
+    pub velocity: Vector2<Fp>,
+    pub angular_velocity: Fp,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: Fp,
+    pub offset: Vector2<Fp>,
+    pub angle: Fp,
+}
+
+/// How a kinematic object moves relative to its rest position over time
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Motion {
+    Static,
+    /// Sinusoidal back and forth movement, e.g. a piston or a sloshing tank
+    Oscillate {
+        amplitude: Vector2<Fp>,
+        period: Fp,
+    },
+    /// Constant rotation about the rest position, e.g. a paddle
+    Rotate {
+        angular_velocity: Fp,
+    },
+    /// Linearly interpolates between keyframes sorted by time, holding the last one afterwards
+    Keyframes(Vec<Keyframe>),
+}
+
+impl Motion {
+    pub fn state(&self, time: Fp) -> MotionState {
+        match self {
+            Motion::Static => MotionState {
+                offset: Vector2::zero(),
+                angle: 0.0,
+                velocity: Vector2::zero(),
+                angular_velocity: 0.0,
+            },
+            Motion::Oscillate { amplitude, period } => {
+                let omega = 2.0 * Fp::PI() / period;
+                MotionState {
+                    offset: amplitude * (omega * time).sin(),
+                    angle: 0.0,
+                    velocity: amplitude * (omega * (omega * time).cos()),
+                    angular_velocity: 0.0,
+                }
+            }
+            Motion::Rotate { angular_velocity } => MotionState {
+                offset: Vector2::zero(),
+                angle: angular_velocity * time,
+                velocity: Vector2::zero(),
+                angular_velocity: *angular_velocity,
+            },
+            Motion::Keyframes(keyframes) => {
+                let next = keyframes.iter().position(|k| k.time > time);
+                match next {
+                    Some(i) if i > 0 => {
+                        let (a, b) = (&keyframes[i - 1], &keyframes[i]);
+                        let duration = b.time - a.time;
+                        let t = (time - a.time) / duration;
+                        MotionState {
+                            offset: a.offset + (b.offset - a.offset) * t,
+                            angle: a.angle + (b.angle - a.angle) * t,
+                            velocity: (b.offset - a.offset) / duration,
+                            angular_velocity: (b.angle - a.angle) / duration,
+                        }
+                    }
+                    // Before the first keyframe or after the last one
+                    _ => {
+                        let keyframe = match next {
+                            Some(_) => keyframes.first(),
+                            None => keyframes.last(),
+                        };
+                        MotionState {
+                            offset: keyframe.map_or(Vector2::zero(), |k| k.offset),
+                            angle: keyframe.map_or(0.0, |k| k.angle),
+                            velocity: Vector2::zero(),
+                            angular_velocity: 0.0,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A thin wall segment driven by a motion script that particles collide with
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KinematicBoundary {
+    pub centre: Vector2<Fp>,
+    pub half_length: Fp,
+    pub angle: Fp,
+    pub motion: Motion,
+}
+
+impl KinematicBoundary {
+    pub fn new(centre: Vector2<Fp>, length: Fp, angle: Fp, motion: Motion) -> Self {
+        KinematicBoundary {
+            centre,
+            half_length: length / 2.0,
+            angle,
+            motion,
+        }
+    }
+
+    /// Returns the segment's end points at `time`
+    pub fn end_points(&self, time: Fp) -> (Vector2<Fp>, Vector2<Fp>) {
+        let (centre, along) = self.placement(time);
+        (centre - along * self.half_length, centre + along * self.half_length)
+    }
+
+    /// The segment's centre and a unit vector along it at `time`
+    fn placement(&self, time: Fp) -> (Vector2<Fp>, Vector2<Fp>) {
+        let state = self.motion.state(time);
+        let angle = self.angle + state.angle;
+        (self.centre + state.offset, Vector2::new(angle.cos(), angle.sin()))
+    }
+
+    /// Pushes particles out of the segment and bounces them off it relative to the wall's own
+    /// velocity, so a moving wall carries particles with it. Particles that crossed the wall
+    /// during the step ending at `time`, because they or the wall moved further than its
+    /// thickness, are caught where they crossed and put back on the side they came from.
+    fn collide(&self, particles: &mut Particles, time: Fp, delta_time: Fp) {
+        let state = self.motion.state(time);
+        let (centre, along) = self.placement(time);
+        let (previous_centre, previous_along) = self.placement(time - delta_time);
+        let (across, previous_across) = (perpendicular(along), perpendicular(previous_along));
+
+        for i in 0..particles.len() {
+            let (pos, vel) = (particles.pos(i), particles.vel(i));
+            // Distances along and out from the wall, at the start of the step from where the
+            // particle's velocity brought it, and now
+            let previous = pos - vel * delta_time - previous_centre;
+            let (previous_dist, previous_side) = (previous.dot(previous_along), previous.dot(previous_across));
+            let offset = pos - centre;
+            let (dist, side) = (offset.dot(along), offset.dot(across));
+
+            let crossed_at = (previous_side * side < 0.0)
+                .then(|| previous_dist + (dist - previous_dist) * previous_side / (previous_side - side))
+                .filter(|crossed_at| crossed_at.abs() <= self.half_length);
+            let (closest, normal) = match crossed_at {
+                Some(crossed_at) => (centre + along * crossed_at, across * previous_side.signum()),
+                None => {
+                    let closest = centre + along * dist.clamp(-self.half_length, self.half_length);
+                    let offset = pos - closest;
+                    let distance = offset.magnitude();
+                    if distance >= BOUNDARY_THICKNESS {
+                        continue;
+                    }
+                    (closest, if distance > 0.0 { offset / distance } else { across })
+                }
+            };
+            particles.set_pos(i, closest + normal * BOUNDARY_THICKNESS);
+
+            let lever = closest - centre;
+            let wall_vel = state.velocity + perpendicular(lever) * state.angular_velocity;
+            let relative_vel = vel - wall_vel;
+            let normal_speed = relative_vel.dot(normal);
+            if normal_speed < 0.0 {
+                particles.set_vel(i, vel - normal * (normal_speed * (1.0 + COEF_OF_REST)));
+            }
+        }
+    }
+}
+
+/// `v` turned a quarter anticlockwise
+fn perpendicular(v: Vector2<Fp>) -> Vector2<Fp> {
+    Vector2::new(-v.y, v.x)
+}
+
+pub fn apply_kinematic_boundaries(scene_data: &mut SceneData, delta_time: Fp) {
+    for boundary in &scene_data.boundaries {
+        boundary.collide(&mut scene_data.particles, scene_data.time, delta_time);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::force_field::ForceField;
+use crate::math::rotate_vector;
+use crate::particle::Particle;
+use crate::physics::{DRAG_COEF, GRAVITY};
+use crate::Fp;
+use cgmath::{InnerSpace, Vector2, Zero};
+use serde::{Deserialize, Serialize};
+
+const GRAVITY_SOFTENING: Fp = 0.05; // Stops point gravity blowing up near its centre
+
+/// How particles interact with the edge of the world along one axis
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BoundaryCondition {
+    /// Particles are repelled by and bounce off the edges
+    Wall,
+    /// Particles leaving one edge re-enter at the opposite edge and interact across the seam
+    Periodic,
+    /// Particles leaving the world are removed
+    Open,
+}
+
+/// How the 2D world maps onto space
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Geometry {
+    /// A flat slice of fluid
+    #[default]
+    Planar,
+    /// A half-plane swept around the y axis, for pipes and jets. x is the distance from the axis,
+    /// which replaces the left wall, and each particle stands for a ring of fluid around it.
+    /// Particle masses are per unit length of the ring, relative to fluid at rest, and each ring
+    /// keeps its total mass, so a particle moving outwards gets lighter as its fluid spreads
+    /// around a longer ring.
+    Axisymmetric,
+}
+
+/// A point that attracts particles
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GravitySource {
+    pub pos: Vector2<Fp>,
+    /// Acceleration at a distance of 1m
+    pub strength: Fp,
+    /// Whether the pull falls off with the square of the distance (a planet) or is the same
+    /// everywhere (radial gravity)
+    pub inverse_square: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Gravity {
+    /// The same acceleration everywhere
+    Uniform(Vector2<Fp>),
+    /// The sum of the pulls of a set of points
+    Sources(Vec<GravitySource>),
+}
+
+impl Gravity {
+    pub fn acceleration_at(&self, pos: Vector2<Fp>) -> Vector2<Fp> {
+        match self {
+            Gravity::Uniform(accel) => *accel,
+            Gravity::Sources(sources) => sources
+                .iter()
+                .map(|source| {
+                    let displacement = source.pos - pos;
+                    let distance = displacement.magnitude().max(GRAVITY_SOFTENING);
+                    let direction = displacement / distance;
+                    if source.inverse_square {
+                        direction * (source.strength / (distance * distance))
+                    } else {
+                        direction * source.strength
+                    }
+                })
+                .fold(Vector2::zero(), |total, accel| total + accel),
+        }
+    }
+
+    /// Potential energy per unit mass, so that `acceleration_at` is its negative gradient. Uniform
+    /// gravity is zero at the origin and point sources are zero infinitely far away.
+    pub fn potential_at(&self, pos: Vector2<Fp>) -> Fp {
+        match self {
+            Gravity::Uniform(accel) => -accel.dot(pos),
+            Gravity::Sources(sources) => sources
+                .iter()
+                .map(|source| {
+                    // Inside the softening radius the pull falls linearly to nothing at the centre,
+                    // which changes the potential's shape
+                    let (distance, s, k) = ((source.pos - pos).magnitude(), GRAVITY_SOFTENING, source.strength);
+                    match (source.inverse_square, distance < s) {
+                        (true, false) => -k / distance,
+                        (true, true) => k * distance * distance / (2.0 * s * s * s) - 1.5 * k / s,
+                        (false, false) => k * distance - k * s / 2.0,
+                        (false, true) => k * distance * distance / (2.0 * s),
+                    }
+                })
+                .sum(),
+        }
+    }
+
+    /// Rotates uniform gravity anticlockwise, e.g. to tilt the tank. Has no effect on point sources.
+    pub fn rotate(&mut self, angle: Fp) {
+        if let Gravity::Uniform(accel) = self {
+            *accel = rotate_vector(accel, angle);
+        }
+    }
+
+    /// Points uniform gravity along `direction`, keeping its strength
+    pub fn point_towards(&mut self, direction: Vector2<Fp>) {
+        if let Gravity::Uniform(accel) = self {
+            if direction.magnitude2() > 0.0 {
+                *accel = direction.normalize() * accel.magnitude();
+            }
+        }
+    }
+}
+
+impl ForceField for Gravity {
+    fn acceleration(&self, particle: &Particle, _time: Fp) -> Vector2<Fp> {
+        self.acceleration_at(particle.pos) * particle.material.gravity_scale()
+    }
+}
+
+/// Simulation parameters that can be changed while the simulation is running
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Strength of the vorticity confinement force, 0 disables it
+    pub vorticity_epsilon: Fp,
+    /// Whether the floor and ceiling heat and cool the particles touching them
+    pub wall_heat_transfer: bool,
+    pub boundary_x: BoundaryCondition,
+    pub boundary_y: BoundaryCondition,
+    pub gravity: Gravity,
+    pub drag_coefficient: Fp,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            vorticity_epsilon: 0.02,
+            wall_heat_transfer: false,
+            boundary_x: BoundaryCondition::Wall,
+            boundary_y: BoundaryCondition::Wall,
+            gravity: Gravity::Uniform(Vector2::new(0.0, GRAVITY)),
+            drag_coefficient: DRAG_COEF,
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use fluid::cursor_tool::Tool;
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overlay {
+    /// Tool and simulation status text
+    Hud,
+    /// Circle showing the tool's radius
+    CursorOutline,
+    /// Kinematic boundaries and the moving tank outline
+    Boundaries,
+    /// Emitters and drains
+    Sources,
+    /// Gravity direction arrow or point source markers
+    Gravity,
+    /// Density (or vorticity in vorticity colour mode) sampled onto a grid behind the particles
+    Heatmap,
+    /// Energy, momentum and density error of the current step under the HUD
+    Diagnostics,
+    /// Min, mean and max time of each physics and rendering stage over the last second
+    Profiler,
+}
+
+/// Which overlays are drawn on top of the particles
+pub struct Overlays {
+    pub hud: bool,
+    pub cursor_outline: bool,
+    pub boundaries: bool,
+    pub sources: bool,
+    pub gravity: bool,
+    pub heatmap: bool,
+    pub diagnostics: bool,
+    pub profiler: bool,
+}
+
+impl Default for Overlays {
+    fn default() -> Self {
+        Overlays {
+            hud: true,
+            cursor_outline: true,
+            boundaries: true,
+            sources: true,
+            gravity: true,
+            heatmap: false,
+            diagnostics: true,
+            profiler: false,
+        }
+    }
+}
+
+impl Overlays {
+    pub fn toggle(&mut self, overlay: Overlay) {
+        let shown = match overlay {
+            Overlay::Hud => &mut self.hud,
+            Overlay::CursorOutline => &mut self.cursor_outline,
+            Overlay::Boundaries => &mut self.boundaries,
+            Overlay::Sources => &mut self.sources,
+            Overlay::Gravity => &mut self.gravity,
+            Overlay::Heatmap => &mut self.heatmap,
+            Overlay::Diagnostics => &mut self.diagnostics,
+            Overlay::Profiler => &mut self.profiler,
+        };
+        *shown = !*shown;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    Pause,
+    /// Advances one step while paused, or one frame when replaying
+    Step,
+    /// Goes back one frame when replaying
+    StepBack,
+    /// Rebuilds the scene from scratch
+    Reset,
+    SaveSnapshot,
+    LoadSnapshot,
+    SlowDown,
+    SpeedUp,
+    ToggleColourMode,
+    ToggleEmitters,
+    ToggleDrains,
+    ToggleOverlay(Overlay),
+    SelectTool(Tool),
+    CycleFalloff,
+    CyclePaintMaterial,
+    IncreaseStrength,
+    DecreaseStrength,
+    /// Held rather than pressed, rotates gravity anticlockwise
+    TiltLeft,
+    /// Held rather than pressed, rotates gravity clockwise
+    TiltRight,
+}
+
+impl Action {
+    /// Parses the names used by `--bind`, e.g. `pause` or `tool_vortex`
+    pub fn from_name(name: &str) -> Option<Action> {
+        let action = match name {
+            "quit" => Action::Quit,
+            "pause" => Action::Pause,
+            "step" => Action::Step,
+            "step_back" => Action::StepBack,
+            "reset" => Action::Reset,
+            "save" => Action::SaveSnapshot,
+            "load" => Action::LoadSnapshot,
+            "slow_down" => Action::SlowDown,
+            "speed_up" => Action::SpeedUp,
+            "colour_mode" => Action::ToggleColourMode,
+            "emitters" => Action::ToggleEmitters,
+            "drains" => Action::ToggleDrains,
+            "overlay_hud" => Action::ToggleOverlay(Overlay::Hud),
+            "overlay_cursor" => Action::ToggleOverlay(Overlay::CursorOutline),
+            "overlay_boundaries" => Action::ToggleOverlay(Overlay::Boundaries),
+            "overlay_sources" => Action::ToggleOverlay(Overlay::Sources),
+            "overlay_gravity" => Action::ToggleOverlay(Overlay::Gravity),
+            "overlay_heatmap" => Action::ToggleOverlay(Overlay::Heatmap),
+            "overlay_diagnostics" => Action::ToggleOverlay(Overlay::Diagnostics),
+            "overlay_profiler" => Action::ToggleOverlay(Overlay::Profiler),
+            "falloff" => Action::CycleFalloff,
+            "paint_material" => Action::CyclePaintMaterial,
+            "strength_up" => Action::IncreaseStrength,
+            "strength_down" => Action::DecreaseStrength,
+            "tilt_left" => Action::TiltLeft,
+            "tilt_right" => Action::TiltRight,
+            _ => {
+                let tool = name.strip_prefix("tool_")?;
+                let tool = Tool::ALL
+                    .into_iter()
+                    .find(|t| t.name().eq_ignore_ascii_case(tool))?;
+                Action::SelectTool(tool)
+            }
+        };
+        Some(action)
+    }
+}
+
+/// Maps keys to actions. Several keys may share an action.
+pub struct KeyBindings {
+    bindings: HashMap<Keycode, Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut bindings = HashMap::from([
+            (Keycode::P, Action::Quit),
+            (Keycode::Escape, Action::Quit),
+            (Keycode::Space, Action::Pause),
+            (Keycode::Period, Action::Step),
+            (Keycode::Comma, Action::StepBack),
+            (Keycode::R, Action::Reset),
+            (Keycode::F6, Action::SaveSnapshot),
+            (Keycode::F9, Action::LoadSnapshot),
+            (Keycode::Minus, Action::SlowDown),
+            (Keycode::Equals, Action::SpeedUp),
+            (Keycode::V, Action::ToggleColourMode),
+            (Keycode::E, Action::ToggleEmitters),
+            (Keycode::D, Action::ToggleDrains),
+            (Keycode::F1, Action::ToggleOverlay(Overlay::Hud)),
+            (Keycode::F2, Action::ToggleOverlay(Overlay::CursorOutline)),
+            (Keycode::F3, Action::ToggleOverlay(Overlay::Boundaries)),
+            (Keycode::F4, Action::ToggleOverlay(Overlay::Sources)),
+            (Keycode::F5, Action::ToggleOverlay(Overlay::Gravity)),
+            (Keycode::F7, Action::ToggleOverlay(Overlay::Heatmap)),
+            (Keycode::F8, Action::ToggleOverlay(Overlay::Diagnostics)),
+            (Keycode::F10, Action::ToggleOverlay(Overlay::Profiler)),
+            (Keycode::F, Action::CycleFalloff),
+            (Keycode::M, Action::CyclePaintMaterial),
+            (Keycode::RightBracket, Action::IncreaseStrength),
+            (Keycode::LeftBracket, Action::DecreaseStrength),
+            (Keycode::Left, Action::TiltLeft),
+            (Keycode::Right, Action::TiltRight),
+        ]);
+        let tool_keys = [
+            Keycode::Num1,
+            Keycode::Num2,
+            Keycode::Num3,
+            Keycode::Num4,
+            Keycode::Num5,
+            Keycode::Num6,
+        ];
+        for (key, tool) in tool_keys.into_iter().zip(Tool::ALL) {
+            bindings.insert(key, Action::SelectTool(tool));
+        }
+        KeyBindings { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Binds `key` to `action`, replacing whatever it was bound to before
+    pub fn bind(&mut self, key: Keycode, action: Action) {
+        self.bindings.insert(key, action);
+    }
+
+    /// Parses and applies a `<action>=<key>` binding, e.g. `pause=P` or `step=Return`.
+    /// Key names are SDL's, see `Keycode::from_name`.
+    pub fn bind_from_str(&mut self, binding: &str) -> Result<(), String> {
+        let (action, key) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("Binding '{}' should look like <action>=<key>", binding))?;
+        let action =
+            Action::from_name(action).ok_or_else(|| format!("Unknown action '{}'", action))?;
+        let key = Keycode::from_name(key).ok_or_else(|| format!("Unknown key '{}'", key))?;
+        self.bind(key, action);
+        Ok(())
+    }
+
+    pub fn action(&self, key: Keycode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    /// Keys bound to `action`, for checking held keys
+    pub fn keys_for(&self, action: Action) -> impl Iterator<Item = Keycode> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| **a == action)
+            .map(|(key, _)| *key)
+    }
+}
@@ -0,0 +1,224 @@
+use crate::force_field::ForceField;
+use crate::material::Material;
+use crate::particle::Particle;
+use crate::scene_data::SceneData;
+use crate::{Fp, CURSOR_FORCE, CURSOR_RADIUS, MAX_PARTICLE_COUNT};
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2, Zero};
+use rand::Rng;
+
+const PAINT_RATE_PER_STRENGTH: Fp = 20.0; // Particles per second painted per unit of strength
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Push,
+    Pull,
+    /// Drags particles along with the cursor
+    Drag,
+    /// Sprays new particles
+    Paint,
+    /// Deletes particles
+    Erase,
+    /// Swirls particles around the cursor
+    Vortex,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 6] = [
+        Tool::Push,
+        Tool::Pull,
+        Tool::Drag,
+        Tool::Paint,
+        Tool::Erase,
+        Tool::Vortex,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Push => "Push",
+            Tool::Pull => "Pull",
+            Tool::Drag => "Drag",
+            Tool::Paint => "Paint",
+            Tool::Erase => "Erase",
+            Tool::Vortex => "Vortex",
+        }
+    }
+}
+
+/// How a tool's strength fades from the cursor to the edge of its radius
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Falloff {
+    Constant,
+    Linear,
+    Smooth,
+}
+
+impl Falloff {
+    /// Weight between 1 at the cursor and 0 at the edge, where `t` is distance / radius
+    pub fn weight(&self, t: Fp) -> Fp {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - t,
+            Falloff::Smooth => {
+                let s = 1.0 - t * t;
+                s * s
+            }
+        }
+    }
+
+    pub fn next(&self) -> Falloff {
+        match self {
+            Falloff::Constant => Falloff::Linear,
+            Falloff::Linear => Falloff::Smooth,
+            Falloff::Smooth => Falloff::Constant,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Falloff::Constant => "Constant",
+            Falloff::Linear => "Linear",
+            Falloff::Smooth => "Smooth",
+        }
+    }
+}
+
+/// The selected tool and its settings
+#[derive(Clone, Copy, Debug)]
+pub struct CursorTool {
+    pub tool: Tool,
+    pub radius: Fp,
+    pub strength: Fp,
+    pub falloff: Falloff,
+    /// Material spawned by the paint tool
+    pub material: Material,
+}
+
+impl Default for CursorTool {
+    fn default() -> Self {
+        CursorTool {
+            tool: Tool::Push,
+            radius: CURSOR_RADIUS,
+            strength: CURSOR_FORCE,
+            falloff: Falloff::Constant,
+            material: Material::Water,
+        }
+    }
+}
+
+pub enum CursorState {
+    /// A mouse button is held. `reverse` flips the tool, e.g. push becomes pull and paint erases.
+    Active {
+        pos: Vector2<Fp>,
+        vel: Vector2<Fp>,
+        reverse: bool,
+        tool: CursorTool,
+    },
+    None,
+}
+
+impl CursorState {
+    pub fn force_field(&self) -> Option<CursorField> {
+        match self {
+            CursorState::Active {
+                pos,
+                vel,
+                reverse,
+                tool,
+            } => {
+                let strength = if *reverse { -tool.strength } else { tool.strength };
+                let tool_type = match tool.tool {
+                    Tool::Push | Tool::Pull | Tool::Drag | Tool::Vortex => tool.tool,
+                    Tool::Paint | Tool::Erase => return None,
+                };
+                Some(CursorField {
+                    tool: tool_type,
+                    centre: *pos,
+                    cursor_vel: *vel,
+                    radius: tool.radius,
+                    strength,
+                    falloff: tool.falloff,
+                })
+            }
+            CursorState::None => None,
+        }
+    }
+}
+
+/// Force applied by the push, pull, drag and vortex tools
+pub struct CursorField {
+    tool: Tool,
+    centre: Vector2<Fp>,
+    cursor_vel: Vector2<Fp>,
+    radius: Fp,
+    strength: Fp,
+    falloff: Falloff,
+}
+
+impl ForceField for CursorField {
+    fn acceleration(&self, particle: &Particle, _time: Fp) -> Vector2<Fp> {
+        let displacement = self.centre - particle.pos;
+        let distance = displacement.magnitude();
+        if distance >= self.radius || distance == 0.0 {
+            return Vector2::zero();
+        }
+        let weight = self.falloff.weight(distance / self.radius);
+        let towards = displacement / distance;
+
+        match self.tool {
+            Tool::Pull => towards * (self.strength * weight),
+            Tool::Push => -towards * (self.strength * weight),
+            // Strength acts as the rate particles are brought up to the cursor's velocity
+            Tool::Drag => (self.cursor_vel - particle.vel) * (self.strength * weight),
+            Tool::Vortex => Vector2::new(towards.y, -towards.x) * (self.strength * weight),
+            Tool::Paint | Tool::Erase => Vector2::zero(),
+        }
+    }
+}
+
+/// Applies the tools that add or remove particles rather than pushing them
+pub fn apply_cursor_tool(scene_data: &mut SceneData, cursor_state: &CursorState, delta_time: Fp) {
+    let CursorState::Active {
+        pos,
+        vel,
+        reverse,
+        tool,
+    } = cursor_state
+    else {
+        return;
+    };
+
+    let erase = match (tool.tool, reverse) {
+        (Tool::Paint, false) | (Tool::Erase, true) => false,
+        (Tool::Paint, true) | (Tool::Erase, false) => true,
+        _ => return,
+    };
+
+    if erase {
+        scene_data
+            .particles
+            .retain(|p| (p.pos - pos).magnitude() >= tool.radius);
+        return;
+    }
+
+    // Spawning is random so fractional particles per step are spawned with matching probability
+    let expected = tool.strength.abs() * PAINT_RATE_PER_STRENGTH * delta_time;
+    let mut count = expected.floor() as usize;
+    if scene_data.rng.gen::<Fp>() < expected.fract() {
+        count += 1;
+    }
+
+    for _ in 0..count {
+        if scene_data.particles.len() >= MAX_PARTICLE_COUNT {
+            break;
+        }
+        // Uniform over the disc around the cursor
+        let angle = scene_data.rng.gen_range(0.0..2.0 * Fp::PI());
+        let distance = tool.radius * scene_data.rng.gen::<Fp>().sqrt();
+        let offset = Vector2::new(angle.cos(), angle.sin()) * distance;
+        let mut particle = Particle::with_material(pos + offset, 1.0, tool.material);
+        particle.vel = *vel;
+        scene_data.particles.push(particle);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::config::Geometry;
+use crate::physics::{ring_radius, REST_DENSITY};
+use crate::scene_data::SceneData;
+use crate::{Fp, WORLD_HEIGHT, WORLD_WIDTH};
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2, Zero};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const CSV_HEADER: &str = "time,particles,kinetic_energy,potential_energy,total_energy,momentum_x,momentum_y,angular_momentum,mean_density_error,max_density_error,max_speed";
+
+/// Whole-scene quantities for judging how physical a run is, e.g. whether energy is conserved.
+/// In an axisymmetric world they're of the whole rings the particles stand for, whose radial
+/// momentum cancels around the axis, as does their angular momentum about the centre of the world.
+#[derive(Clone, Copy, Debug)]
+pub struct Diagnostics {
+    pub time: Fp,
+    pub particle_count: usize,
+    pub kinetic_energy: Fp,
+    /// From the scene's gravity, scaled by each material's gravity scale
+    pub potential_energy: Fp,
+    pub momentum: Vector2<Fp>,
+    /// About the centre of the world, anticlockwise positive
+    pub angular_momentum: Fp,
+    /// Mean of |density - rest density| / rest density. Uses the densities from the last step,
+    /// and includes particles at the free surface, which are always below rest density.
+    pub mean_density_error: Fp,
+    pub max_density_error: Fp,
+    pub max_speed: Fp,
+}
+
+impl Diagnostics {
+    pub fn compute(scene_data: &SceneData) -> Self {
+        let centre = Vector2::new(WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+        let gravity = &scene_data.config.gravity;
+        let axisymmetric = scene_data.geometry == Geometry::Axisymmetric;
+        let mut diagnostics = Diagnostics {
+            time: scene_data.time,
+            particle_count: scene_data.particles.len(),
+            kinetic_energy: 0.0,
+            potential_energy: 0.0,
+            momentum: Vector2::zero(),
+            angular_momentum: 0.0,
+            mean_density_error: 0.0,
+            max_density_error: 0.0,
+            max_speed: 0.0,
+        };
+
+        for particle in &scene_data.particles {
+            let arm = particle.pos - centre;
+            let density_error = (particle.density - REST_DENSITY).abs() / REST_DENSITY;
+            // Particle masses are per unit length of their rings
+            let mass = if axisymmetric {
+                2.0 * Fp::PI() * ring_radius(particle.pos.x) * particle.mass
+            } else {
+                particle.mass
+            };
+            diagnostics.kinetic_energy += 0.5 * mass * particle.vel.magnitude2();
+            diagnostics.potential_energy +=
+                mass * particle.material.gravity_scale() * gravity.potential_at(particle.pos);
+            if axisymmetric {
+                diagnostics.momentum.y += particle.vel.y * mass;
+            } else {
+                diagnostics.momentum += particle.vel * mass;
+                diagnostics.angular_momentum += mass * (arm.x * particle.vel.y - arm.y * particle.vel.x);
+            }
+            diagnostics.mean_density_error += density_error;
+            diagnostics.max_density_error = diagnostics.max_density_error.max(density_error);
+            diagnostics.max_speed = diagnostics.max_speed.max(particle.vel.magnitude());
+        }
+        if !scene_data.particles.is_empty() {
+            diagnostics.mean_density_error /= scene_data.particles.len() as Fp;
+        }
+        diagnostics
+    }
+
+    pub fn total_energy(&self) -> Fp {
+        self.kinetic_energy + self.potential_energy
+    }
+}
+
+/// Writes one row of diagnostics per call to a CSV file
+pub struct DiagnosticsLog {
+    writer: BufWriter<File>,
+}
+
+impl DiagnosticsLog {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", CSV_HEADER)?;
+        Ok(DiagnosticsLog { writer })
+    }
+
+    pub fn record(&mut self, diagnostics: &Diagnostics) -> io::Result<()> {
+        let d = diagnostics;
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            d.time,
+            d.particle_count,
+            d.kinetic_energy,
+            d.potential_energy,
+            d.total_energy(),
+            d.momentum.x,
+            d.momentum.y,
+            d.angular_momentum,
+            d.mean_density_error,
+            d.max_density_error,
+            d.max_speed,
+        )
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,100 @@
+use crate::material::Material;
+use crate::particle::Particle;
+use crate::scene_data::SceneData;
+use crate::{Fp, MAX_PARTICLE_COUNT};
+use cgmath::{InnerSpace, Vector2};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Spawns a stream of particles, e.g. a tap
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Emitter {
+    pub pos: Vector2<Fp>,
+    pub direction: Vector2<Fp>,
+    /// Particles spawned per second
+    pub rate: Fp,
+    pub speed: Fp,
+    pub material: Material,
+    /// Width of the nozzle particles are randomly spread across
+    pub jitter: Fp,
+    pub enabled: bool,
+    /// Fraction of a particle carried over between steps so low rates still emit
+    pending: Fp,
+}
+
+impl Emitter {
+    pub fn new(
+        pos: Vector2<Fp>,
+        direction: Vector2<Fp>,
+        rate: Fp,
+        speed: Fp,
+        material: Material,
+        jitter: Fp,
+    ) -> Self {
+        Emitter {
+            pos,
+            direction: direction.normalize(),
+            rate,
+            speed,
+            material,
+            jitter,
+            enabled: true,
+            pending: 0.0,
+        }
+    }
+}
+
+/// Removes any particle that enters its rectangle, e.g. a plug hole
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Drain {
+    pub min: Vector2<Fp>,
+    pub max: Vector2<Fp>,
+    pub enabled: bool,
+}
+
+impl Drain {
+    pub fn new(min: Vector2<Fp>, max: Vector2<Fp>) -> Self {
+        Drain {
+            min,
+            max,
+            enabled: true,
+        }
+    }
+
+    pub fn contains(&self, pos: Vector2<Fp>) -> bool {
+        pos.x >= self.min.x && pos.x <= self.max.x && pos.y >= self.min.y && pos.y <= self.max.y
+    }
+}
+
+pub fn apply_emitters(scene_data: &mut SceneData, delta_time: Fp) {
+    for emitter in scene_data.emitters.iter_mut().filter(|e| e.enabled) {
+        emitter.pending += emitter.rate * delta_time;
+
+        let across = Vector2::new(-emitter.direction.y, emitter.direction.x);
+        while emitter.pending >= 1.0 {
+            emitter.pending -= 1.0;
+            if scene_data.particles.len() >= MAX_PARTICLE_COUNT {
+                continue;
+            }
+
+            let offset = if emitter.jitter > 0.0 {
+                scene_data
+                    .rng
+                    .gen_range(-emitter.jitter / 2.0..emitter.jitter / 2.0)
+            } else {
+                0.0
+            };
+            let mut particle =
+                Particle::with_material(emitter.pos + across * offset, 1.0, emitter.material);
+            particle.vel = emitter.direction * emitter.speed;
+            scene_data.particles.push(particle);
+        }
+    }
+}
+
+pub fn apply_drains(scene_data: &mut SceneData) {
+    let drains = &scene_data.drains;
+    scene_data
+        .particles
+        .retain(|p| !drains.iter().any(|d| d.enabled && d.contains(p.pos)));
+}
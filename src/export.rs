@@ -0,0 +1,367 @@
+use crate::config::SimulationConfig;
+use crate::numpy::{save_npy, NpzWriter};
+use crate::particle::{Particle, Particles};
+use crate::sampling::{GridSampler, GridSpec};
+use crate::scene_data::SceneData;
+use crate::Fp;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_EXPORT_INTERVAL: u32 = 10; // Steps between exported frames
+
+// Names of `Fp` in the headers of the text formats
+const FLOAT_TYPE: &str = if cfg!(feature = "f64") { "double" } else { "float" }; // Legacy VTK and PLY
+const VTP_FLOAT_TYPE: &str = if cfg!(feature = "f64") { "Float64" } else { "Float32" };
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Csv,
+    /// Legacy ASCII `.vtk` polydata
+    VtkLegacy,
+    /// XML VTK polydata
+    Vtp,
+    /// ASCII PLY point cloud
+    Ply,
+    /// One NumPy `.npy` file per array, e.g. `frame_000000_pos.npy`
+    Npy,
+    /// All of a frame's arrays in one NumPy `.npz` archive
+    Npz,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name {
+            "csv" => Some(ExportFormat::Csv),
+            "vtk" => Some(ExportFormat::VtkLegacy),
+            "vtp" => Some(ExportFormat::Vtp),
+            "ply" => Some(ExportFormat::Ply),
+            "npy" => Some(ExportFormat::Npy),
+            "npz" => Some(ExportFormat::Npz),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::VtkLegacy => "vtk",
+            ExportFormat::Vtp => "vtp",
+            ExportFormat::Ply => "ply",
+            ExportFormat::Npy => "npy",
+            ExportFormat::Npz => "npz",
+        }
+    }
+}
+
+/// Written to `metadata.json` next to the frames so they can be interpreted later
+#[derive(Serialize)]
+pub struct ExportMetadata {
+    pub scene: String,
+    pub seed: u64,
+    /// Fixed time step, frames record their own time as the viewer steps by real time
+    pub delta_time: Fp,
+    /// Steps between frames
+    pub interval: u32,
+    pub format: &'static str,
+    /// Per-particle arrays in each frame, `pos` and `vel` are (N, 2) and the rest (N,)
+    pub arrays: Vec<String>,
+    /// Grid the `grid_*` arrays were sampled on, they're (rows, columns) with row 0 at the bottom
+    /// and `grid_velocity` has a trailing axis of 2
+    pub grid: Option<GridSpec>,
+    pub config: SimulationConfig,
+}
+
+/// A named per-particle scalar written alongside position and velocity
+pub struct Attribute {
+    pub name: String,
+    pub value: Box<dyn Fn(&Particle) -> Fp>,
+}
+
+impl Attribute {
+    pub fn new(name: &str, value: impl Fn(&Particle) -> Fp + 'static) -> Self {
+        Attribute {
+            name: name.to_string(),
+            value: Box::new(value),
+        }
+    }
+}
+
+/// Writes the particles to numbered files in a directory every `interval` steps
+pub struct ParticleExporter {
+    pub format: ExportFormat,
+    pub directory: PathBuf,
+    pub interval: u32,
+    /// Density, pressure, vorticity, temperature and material (as its index) by default
+    pub attributes: Vec<Attribute>,
+    /// Fields sampled onto a grid, only written by the NumPy formats
+    pub grid: Option<GridSampler>,
+    steps: u64,
+    frames: u64,
+}
+
+impl ParticleExporter {
+    pub fn new(format: ExportFormat, directory: impl Into<PathBuf>, interval: u32) -> Self {
+        ParticleExporter {
+            format,
+            directory: directory.into(),
+            interval: interval.max(1),
+            attributes: vec![
+                Attribute::new("density", |p| p.density),
+                Attribute::new("pressure", |p| p.pressure),
+                Attribute::new("vorticity", |p| p.vorticity),
+                Attribute::new("temperature", |p| p.temperature),
+                Attribute::new("material", |p| p.material as u8 as Fp),
+            ],
+            grid: None,
+            steps: 0,
+            frames: 0,
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &str, value: impl Fn(&Particle) -> Fp + 'static) -> Self {
+        self.attributes.push(Attribute::new(name, value));
+        self
+    }
+
+    pub fn with_grid(mut self, spec: GridSpec) -> Self {
+        self.grid = Some(GridSampler::new(spec));
+        self
+    }
+
+    /// Writes `metadata.json` into the export directory
+    pub fn write_metadata(
+        &self,
+        scene: &str,
+        seed: u64,
+        delta_time: Fp,
+        config: &SimulationConfig,
+    ) -> io::Result<()> {
+        let mut arrays = vec![String::from("pos"), String::from("vel")];
+        arrays.extend(self.attributes.iter().map(|a| a.name.clone()));
+        let metadata = ExportMetadata {
+            scene: scene.to_string(),
+            seed,
+            delta_time,
+            interval: self.interval,
+            format: self.format.extension(),
+            arrays,
+            grid: self.grid.as_ref().map(|g| g.spec),
+            config: config.clone(),
+        };
+
+        fs::create_dir_all(&self.directory)?;
+        let writer = BufWriter::new(File::create(self.directory.join("metadata.json"))?);
+        serde_json::to_writer_pretty(writer, &metadata).map_err(io::Error::from)
+    }
+
+    /// Call once per physics step, every `interval`th call writes `frame_000000.<ext>`,
+    /// `frame_000001.<ext>` and so on
+    pub fn export_step(&mut self, scene_data: &SceneData) -> io::Result<()> {
+        if self.steps.is_multiple_of(self.interval as u64) {
+            fs::create_dir_all(&self.directory)?;
+            let path = self
+                .directory
+                .join(format!("frame_{:06}.{}", self.frames, self.format.extension()));
+            self.export_frame(scene_data, path)?;
+            self.frames += 1;
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    pub fn export_frame(&mut self, scene_data: &SceneData, path: impl AsRef<Path>) -> io::Result<()> {
+        let (particles, time) = (&scene_data.particles, scene_data.time);
+        match self.format {
+            ExportFormat::Csv => write_text_file(path, |w| self.write_csv(w, particles)),
+            ExportFormat::VtkLegacy => write_text_file(path, |w| self.write_vtk_legacy(w, particles, time)),
+            ExportFormat::Vtp => write_text_file(path, |w| self.write_vtp(w, particles, time)),
+            ExportFormat::Ply => write_text_file(path, |w| self.write_ply(w, particles, time)),
+            ExportFormat::Npy => self.write_npy_files(scene_data, path.as_ref()),
+            ExportFormat::Npz => self.write_npz(scene_data, path),
+        }
+    }
+
+    fn write_csv(&self, w: &mut impl Write, particles: &Particles) -> io::Result<()> {
+        write!(w, "x,y,vx,vy")?;
+        for attribute in &self.attributes {
+            write!(w, ",{}", attribute.name)?;
+        }
+        writeln!(w)?;
+
+        for p in particles {
+            write!(w, "{},{},{},{}", p.pos.x, p.pos.y, p.vel.x, p.vel.y)?;
+            for attribute in &self.attributes {
+                write!(w, ",{}", (attribute.value)(&p))?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    fn write_vtk_legacy(&self, w: &mut impl Write, particles: &Particles, time: Fp) -> io::Result<()> {
+        let n = particles.len();
+        writeln!(w, "# vtk DataFile Version 3.0")?;
+        writeln!(w, "fluid particles t={}", time)?;
+        writeln!(w, "ASCII")?;
+        writeln!(w, "DATASET POLYDATA")?;
+        writeln!(w, "POINTS {} {}", n, FLOAT_TYPE)?;
+        for p in particles {
+            writeln!(w, "{} {} 0", p.pos.x, p.pos.y)?;
+        }
+        writeln!(w, "VERTICES {} {}", n, 2 * n)?;
+        for i in 0..n {
+            writeln!(w, "1 {}", i)?;
+        }
+
+        writeln!(w, "POINT_DATA {}", n)?;
+        writeln!(w, "VECTORS velocity {}", FLOAT_TYPE)?;
+        for p in particles {
+            writeln!(w, "{} {} 0", p.vel.x, p.vel.y)?;
+        }
+        for attribute in &self.attributes {
+            writeln!(w, "SCALARS {} {} 1", attribute.name, FLOAT_TYPE)?;
+            writeln!(w, "LOOKUP_TABLE default")?;
+            for p in particles {
+                writeln!(w, "{}", (attribute.value)(&p))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_vtp(&self, w: &mut impl Write, particles: &Particles, time: Fp) -> io::Result<()> {
+        let n = particles.len();
+        writeln!(w, r#"<?xml version="1.0"?>"#)?;
+        writeln!(w, r#"<VTKFile type="PolyData" version="0.1" byte_order="LittleEndian">"#)?;
+        writeln!(w, "<PolyData>")?;
+        writeln!(w, r#"<FieldData><DataArray type="{}" Name="TimeValue" NumberOfTuples="1">{}</DataArray></FieldData>"#, VTP_FLOAT_TYPE, time)?;
+        writeln!(w, r#"<Piece NumberOfPoints="{}" NumberOfVerts="{}">"#, n, n)?;
+
+        writeln!(w, "<Points>")?;
+        writeln!(w, r#"<DataArray type="{}" NumberOfComponents="3" format="ascii">"#, VTP_FLOAT_TYPE)?;
+        for p in particles {
+            writeln!(w, "{} {} 0", p.pos.x, p.pos.y)?;
+        }
+        writeln!(w, "</DataArray>")?;
+        writeln!(w, "</Points>")?;
+
+        writeln!(w, "<Verts>")?;
+        writeln!(w, r#"<DataArray type="Int32" Name="connectivity" format="ascii">"#)?;
+        for i in 0..n {
+            writeln!(w, "{}", i)?;
+        }
+        writeln!(w, "</DataArray>")?;
+        writeln!(w, r#"<DataArray type="Int32" Name="offsets" format="ascii">"#)?;
+        for i in 1..=n {
+            writeln!(w, "{}", i)?;
+        }
+        writeln!(w, "</DataArray>")?;
+        writeln!(w, "</Verts>")?;
+
+        writeln!(w, r#"<PointData Vectors="velocity">"#)?;
+        writeln!(w, r#"<DataArray type="{}" Name="velocity" NumberOfComponents="3" format="ascii">"#, VTP_FLOAT_TYPE)?;
+        for p in particles {
+            writeln!(w, "{} {} 0", p.vel.x, p.vel.y)?;
+        }
+        writeln!(w, "</DataArray>")?;
+        for attribute in &self.attributes {
+            writeln!(w, r#"<DataArray type="{}" Name="{}" format="ascii">"#, VTP_FLOAT_TYPE, attribute.name)?;
+            for p in particles {
+                writeln!(w, "{}", (attribute.value)(&p))?;
+            }
+            writeln!(w, "</DataArray>")?;
+        }
+        writeln!(w, "</PointData>")?;
+
+        writeln!(w, "</Piece>")?;
+        writeln!(w, "</PolyData>")?;
+        writeln!(w, "</VTKFile>")
+    }
+
+    fn write_ply(&self, w: &mut impl Write, particles: &Particles, time: Fp) -> io::Result<()> {
+        writeln!(w, "ply")?;
+        writeln!(w, "format ascii 1.0")?;
+        writeln!(w, "comment time {}", time)?;
+        writeln!(w, "element vertex {}", particles.len())?;
+        for property in ["x", "y", "z", "vx", "vy", "vz"] {
+            writeln!(w, "property {} {}", FLOAT_TYPE, property)?;
+        }
+        for attribute in &self.attributes {
+            writeln!(w, "property {} {}", FLOAT_TYPE, attribute.name)?;
+        }
+        writeln!(w, "end_header")?;
+
+        for p in particles {
+            write!(w, "{} {} 0 {} {} 0", p.pos.x, p.pos.y, p.vel.x, p.vel.y)?;
+            for attribute in &self.attributes {
+                write!(w, " {}", (attribute.value)(&p))?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    /// Every array of the frame as (name, shape, data), with the time as a scalar
+    fn arrays(&mut self, scene_data: &SceneData) -> Vec<(String, Vec<usize>, Vec<Fp>)> {
+        let particles = &scene_data.particles;
+        let n = particles.len();
+        let mut arrays = vec![
+            (String::from("time"), vec![], vec![scene_data.time]),
+            (
+                String::from("pos"),
+                vec![n, 2],
+                particles.iter().flat_map(|p| [p.pos.x, p.pos.y]).collect(),
+            ),
+            (
+                String::from("vel"),
+                vec![n, 2],
+                particles.iter().flat_map(|p| [p.vel.x, p.vel.y]).collect(),
+            ),
+        ];
+        for attribute in &self.attributes {
+            arrays.push((
+                attribute.name.clone(),
+                vec![n],
+                particles.iter().map(|p| (attribute.value)(&p)).collect(),
+            ));
+        }
+
+        if let Some(sampler) = &self.grid {
+            let fields = sampler.sample(scene_data);
+            let shape = vec![fields.spec.rows, fields.spec.columns];
+            let velocity = fields.velocity.iter().flat_map(|v| [v.x, v.y]).collect();
+            arrays.push((String::from("grid_density"), shape.clone(), fields.density));
+            arrays.push((String::from("grid_velocity"), vec![shape[0], shape[1], 2], velocity));
+            arrays.push((String::from("grid_pressure"), shape.clone(), fields.pressure));
+            arrays.push((String::from("grid_vorticity"), shape, fields.vorticity));
+        }
+        arrays
+    }
+
+    /// Writes `<stem>_<array>.npy` for each array, where `path` is the frame's `.npy` path
+    fn write_npy_files(&mut self, scene_data: &SceneData, path: &Path) -> io::Result<()> {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("frame");
+        for (name, shape, data) in self.arrays(scene_data) {
+            save_npy(path.with_file_name(format!("{}_{}.npy", stem, name)), &shape, &data)?;
+        }
+        Ok(())
+    }
+
+    fn write_npz(&mut self, scene_data: &SceneData, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut npz = NpzWriter::create(path)?;
+        for (name, shape, data) in self.arrays(scene_data) {
+            npz.add_array(&name, &shape, &data)?;
+        }
+        npz.finish()
+    }
+}
+
+fn write_text_file(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()
+}
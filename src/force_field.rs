@@ -0,0 +1,147 @@
+use crate::math::Vector;
+use crate::particle::Particle;
+use crate::Fp;
+use cgmath::{InnerSpace, Vector2, Zero};
+
+/// Something that accelerates particles, e.g. gravity, drag or a fan. Any number can be
+/// registered on a scene with `SceneData::with_force_field`. Fields are evaluated from several
+/// threads at once. Fields act on 2D particles unless `V` is a 3D vector.
+pub trait ForceField<V: Vector = Vector2<Fp>>: Send + Sync {
+    /// Acceleration the field applies to `particle` at simulation time `time`
+    fn acceleration(&self, particle: &Particle<V>, time: Fp) -> V;
+}
+
+/// The same acceleration everywhere
+pub struct UniformField<V: Vector = Vector2<Fp>> {
+    pub accel: V,
+}
+
+impl<V: Vector> ForceField<V> for UniformField<V> {
+    fn acceleration(&self, _particle: &Particle<V>, _time: Fp) -> V {
+        self.accel
+    }
+}
+
+/// Constant strength pull towards (or push away from, when `strength` is negative) a point
+pub struct RadialField {
+    pub centre: Vector2<Fp>,
+    pub strength: Fp,
+    /// Particles further away than this are unaffected
+    pub radius: Fp,
+}
+
+impl ForceField for RadialField {
+    fn acceleration(&self, particle: &Particle, _time: Fp) -> Vector2<Fp> {
+        let displacement = self.centre - particle.pos;
+        let distance = displacement.magnitude();
+        if distance >= self.radius || distance == 0.0 {
+            return Vector2::zero();
+        }
+        displacement / distance * self.strength
+    }
+}
+
+/// Swirls particles anticlockwise (clockwise when `strength` is negative) around a point
+pub struct VortexField {
+    pub centre: Vector2<Fp>,
+    pub strength: Fp,
+    pub radius: Fp,
+}
+
+impl ForceField for VortexField {
+    fn acceleration(&self, particle: &Particle, _time: Fp) -> Vector2<Fp> {
+        let displacement = particle.pos - self.centre;
+        let distance = displacement.magnitude();
+        if distance >= self.radius || distance == 0.0 {
+            return Vector2::zero();
+        }
+        // Strongest halfway out, fading to nothing at the centre and the edge
+        let profile = 1.0 - (2.0 * distance / self.radius - 1.0).abs();
+        Vector2::new(-displacement.y, displacement.x) / distance * (self.strength * profile)
+    }
+}
+
+/// Smoothly varying random push that drifts over time
+pub struct TurbulenceField {
+    pub strength: Fp,
+    /// Size of the eddies in metres
+    pub scale: Fp,
+    /// How quickly the pattern changes, in changes per second
+    pub frequency: Fp,
+    pub seed: u32,
+}
+
+impl ForceField for TurbulenceField {
+    fn acceleration(&self, particle: &Particle, time: Fp) -> Vector2<Fp> {
+        let (x, y, t) = (
+            particle.pos.x / self.scale,
+            particle.pos.y / self.scale,
+            time * self.frequency,
+        );
+        Vector2::new(
+            value_noise(x, y, t, self.seed),
+            value_noise(x, y, t, self.seed.wrapping_add(1)),
+        ) * self.strength
+    }
+}
+
+/// Limits another field to a rectangle
+pub struct RegionField {
+    pub min: Vector2<Fp>,
+    pub max: Vector2<Fp>,
+    pub field: Box<dyn ForceField>,
+}
+
+impl ForceField for RegionField {
+    fn acceleration(&self, particle: &Particle, time: Fp) -> Vector2<Fp> {
+        let pos = particle.pos;
+        if pos.x < self.min.x || pos.x > self.max.x || pos.y < self.min.y || pos.y > self.max.y {
+            return Vector2::zero();
+        }
+        self.field.acceleration(particle, time)
+    }
+}
+
+/// Slows particles in proportion to their speed, at the same rate whatever their mass, as the
+/// drag stands in for losses throughout the fluid
+pub struct Drag {
+    pub coefficient: Fp,
+}
+
+impl<V: Vector> ForceField<V> for Drag {
+    fn acceleration(&self, particle: &Particle<V>, _time: Fp) -> V {
+        -particle.vel * self.coefficient
+    }
+}
+
+/// Smoothly interpolated random values in -1..1 on an integer lattice
+fn value_noise(x: Fp, y: Fp, z: Fp, seed: u32) -> Fp {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let smooth = |t: Fp| t * t * (3.0 - 2.0 * t);
+    let (tx, ty, tz) = (smooth(x - x0), smooth(y - y0), smooth(z - z0));
+    let (x0, y0, z0) = (x0 as i32, y0 as i32, z0 as i32);
+
+    let lerp = |a: Fp, b: Fp, t: Fp| a + (b - a) * t;
+    let corner = |dx: i32, dy: i32, dz: i32| lattice_value(x0 + dx, y0 + dy, z0 + dz, seed);
+    let face = |dz: i32| {
+        lerp(
+            lerp(corner(0, 0, dz), corner(1, 0, dz), tx),
+            lerp(corner(0, 1, dz), corner(1, 1, dz), tx),
+            ty,
+        )
+    };
+    lerp(face(0), face(1), tz)
+}
+
+fn lattice_value(x: i32, y: i32, z: i32, seed: u32) -> Fp {
+    let mut hash = seed.wrapping_mul(0x9E37_79B1)
+        ^ (x as u32).wrapping_mul(0x85EB_CA6B)
+        ^ (y as u32).wrapping_mul(0xC2B2_AE35)
+        ^ (z as u32).wrapping_mul(0x27D4_EB2F);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    hash = hash.wrapping_mul(0x297A_2D39);
+    hash ^= hash >> 15;
+    (hash as Fp / u32::MAX as Fp) * 2.0 - 1.0
+}
@@ -0,0 +1,36 @@
+use crate::math::Vector;
+use crate::physics::INTERACTION_RADIUS;
+use crate::Fp;
+use cgmath::num_traits::FloatConst;
+
+// SPH smoothing kernels (Müller et al. 2003) with support radius INTERACTION_RADIUS, normalised
+// for 2D or 3D to suit the vectors they're given
+
+const H: Fp = INTERACTION_RADIUS;
+
+/// Weight of a neighbour at `displacement`
+pub fn poly6<V: Vector>(displacement: V) -> Fp {
+    // Clamped rather than returning early for r >= H, so loops over neighbours vectorise
+    let diff = (H * H - displacement.magnitude2()).max(0.0);
+    let scale = by_dimension::<V>(4.0 / (Fp::PI() * H.powi(8)), 315.0 / (64.0 * Fp::PI() * H.powi(9)));
+    scale * diff * diff * diff
+}
+
+/// Gradient of the spiky kernel with respect to the first particle, where `displacement` is `pos_i - pos_j`
+pub fn spiky_gradient<V: Vector>(displacement: V) -> V {
+    let r = displacement.magnitude();
+    if r >= H || r == 0.0 {
+        return V::zero();
+    }
+    let diff = H - r;
+    let scale = by_dimension::<V>(-30.0 / (Fp::PI() * H.powi(5)), -45.0 / (Fp::PI() * H.powi(6)));
+    displacement * (scale * diff * diff / r)
+}
+
+fn by_dimension<V: Vector>(two_d: Fp, three_d: Fp) -> Fp {
+    if V::DIMENSIONS == 2 {
+        two_d
+    } else {
+        three_d
+    }
+}
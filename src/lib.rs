@@ -0,0 +1,41 @@
+pub mod boundary;
+pub mod config;
+pub mod cursor_tool;
+pub mod diagnostics;
+pub mod emitter;
+pub mod export;
+pub mod force_field;
+pub mod kernels;
+pub mod material;
+pub mod math;
+pub mod neighbour_grid;
+pub mod numpy;
+pub mod particle;
+pub mod physics;
+pub mod probe;
+pub mod profiler;
+pub mod sampling;
+pub mod scene_data;
+pub mod scenes;
+pub mod snapshot;
+pub mod thermal;
+pub mod three_d;
+pub mod trajectory;
+
+/// Floating point type used throughout the simulation, double precision with the `f64` feature
+#[cfg(not(feature = "f64"))]
+pub type Fp = f32;
+#[cfg(feature = "f64")]
+pub type Fp = f64;
+
+pub const SCREEN_WIDTH: u32 = 1000;
+pub const SCREEN_HEIGHT: u32 = 1000;
+
+pub const WORLD_HEIGHT: Fp = 1.0; // Screen height in metres
+pub const WORLD_WIDTH: Fp = (SCREEN_WIDTH as Fp / SCREEN_HEIGHT as Fp) * WORLD_HEIGHT;
+
+pub const PARTICLE_COUNT: usize = 2000;
+pub const MAX_PARTICLE_COUNT: usize = 4000; // Emitters stop spawning past this
+
+pub const CURSOR_FORCE: Fp = 12.0;
+pub const CURSOR_RADIUS: Fp = 0.3;
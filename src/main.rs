@@ -1,105 +1,497 @@
 #![allow(dead_code)]
 
-use crate::math::{generate_triangle, screen_to_world, world_to_open_gl};
-use crate::physics::physics_update;
-use crate::renderer::render_scene_data;
-use crate::scene_data::{SceneData, SpawningMethod};
+use crate::controls::{Action, KeyBindings, Overlays};
+use crate::hud::text_quads;
 use crate::sdl2_interface::init_sdl2;
-use cgmath::{InnerSpace, Vector2, Zero};
-use gl::types::{GLfloat, GLsizei};
-use rand::Rng;
+use fluid::boundary::Motion;
+use fluid::config::{Geometry, Gravity};
+use fluid::cursor_tool::{CursorState, CursorTool};
+use fluid::diagnostics::{Diagnostics, DiagnosticsLog};
+use fluid::export::{ExportFormat, ParticleExporter, DEFAULT_EXPORT_INTERVAL};
+use fluid::material::Material;
+use fluid::math::{generate_triangle, screen_to_world, world_to_open_gl};
+use fluid::physics::{physics_update_profiled, INTERACTION_RADIUS, REST_DENSITY};
+use fluid::probe::{Probe, ProbeLog};
+use fluid::profiler::Profiler;
+use fluid::sampling::{GridSampler, GridSpec};
+use fluid::scene_data::SceneData;
+use fluid::scenes::Scene;
+use fluid::snapshot::Snapshot;
+use fluid::trajectory::{Trajectory, TrajectoryRecorder, DEFAULT_RECORD_INTERVAL};
+use fluid::{Fp, SCREEN_HEIGHT, SCREEN_WIDTH, WORLD_HEIGHT, WORLD_WIDTH};
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2};
+use gl::types::GLsizei;
 use sdl2::event::Event;
 use sdl2::gfx::framerate::FPSManager;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::TimerSubsystem;
+use sdl2::keyboard::Scancode;
+use std::time::Instant;
 
-mod math;
-mod particle;
-mod physics;
+mod controls;
+mod hud;
 mod renderer;
-mod scene_data;
 mod sdl2_interface;
 mod opengl_interface;
+mod viewer_3d;
 
-pub type Fp = f32;
+pub const TARGET_FPS: u32 = 200;
 
-pub const SCREEN_WIDTH: u32 = 1000;
-pub const SCREEN_HEIGHT: u32 = 1000;
+pub const DEFAULT_SNAPSHOT_PATH: &str = "snapshot.bin"; // Used by the save and load keys unless --save is given
 
-pub const WORLD_HEIGHT: Fp = 1.0; // Screen height in metres
-pub const WORLD_WIDTH: Fp = (SCREEN_WIDTH as Fp / SCREEN_HEIGHT as Fp) * WORLD_HEIGHT;
+pub const CURSOR_SCROLL_SCALE: Fp = 1.1; // Radius multiplier per scroll wheel notch
+pub const CURSOR_STRENGTH_STEP: Fp = 1.25; // Strength multiplier per bracket key press
 
-pub const PARTICLE_COUNT: usize = 2000;
+pub const TIME_SCALE_STEP: Fp = 2.0; // Multiplier per slow down or speed up key press
+pub const MIN_TIME_SCALE: Fp = 1.0 / 16.0;
+pub const MAX_TIME_SCALE: Fp = 4.0;
 
-pub const TARGET_FPS: u32 = 200;
+pub const GRAVITY_TILT_SPEED: Fp = 1.0; // Radians per second while an arrow key is held
 
-pub const CURSOR_FORCE: Fp = 12.0;
-pub const CURSOR_RADIUS: Fp = 0.3;
+pub const VORTICITY_COLOUR_SCALE: Fp = 20.0; // Vorticity at which particles are fully coloured
+pub const HEATMAP_RESOLUTION: usize = 50; // Heatmap cells along each side of the world
 
 pub const USE_TRUE_DELTA_TIME: bool = true;
 pub const USE_SDL2_DELAY: bool = false;
 
-pub enum CursorState {
-    Push(Vector2<Fp>),
-    Pull(Vector2<Fp>),
-    None,
+pub enum ColourMode {
+    Velocity,
+    Vorticity,
 }
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut scene = Scene::Random;
+    let mut scene_3d = None;
+    let mut key_bindings = KeyBindings::default();
+    let mut seed: u64 = rand::random();
+    let mut load_path = None;
+    let mut snapshot_path = String::from(DEFAULT_SNAPSHOT_PATH);
+    let mut headless_steps = None;
+    let mut record_path = None;
+    let mut record_interval = DEFAULT_RECORD_INTERVAL;
+    let mut replay_path = None;
+    let mut export_directory = None;
+    let mut export_format = ExportFormat::Csv;
+    let mut export_interval = DEFAULT_EXPORT_INTERVAL;
+    let mut export_grid = None;
+    let mut probe_specs = Vec::new();
+    let mut probes_csv_path = None;
+    let mut diagnostics_path = None;
+    let mut trace_path = None;
+    let mut thread_count = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scene" => {
+                let name = args.next().expect("--scene requires a scene name");
+                match Scene::from_name(&name) {
+                    Some(named) => scene = named,
+                    None => {
+                        scene_3d = Some(
+                            fluid::three_d::scenes::Scene::from_name(&name)
+                                .unwrap_or_else(|| panic!("Unknown scene '{}'", name)),
+                        )
+                    }
+                }
+            }
+            "--bind" => {
+                let binding = args.next().expect("--bind requires <action>=<key>");
+                key_bindings.bind_from_str(&binding).unwrap_or_else(|e| panic!("{}", e));
+            }
+            "--seed" => {
+                let value = args.next().expect("--seed requires a number");
+                seed = value.parse().unwrap_or_else(|_| panic!("Invalid seed '{}'", value));
+            }
+            "--load" => load_path = Some(args.next().expect("--load requires a path")),
+            "--save" => snapshot_path = args.next().expect("--save requires a path"),
+            "--headless" => {
+                let value = args.next().expect("--headless requires a step count");
+                headless_steps =
+                    Some(value.parse().unwrap_or_else(|_| panic!("Invalid step count '{}'", value)));
+            }
+            "--record" => record_path = Some(args.next().expect("--record requires a path")),
+            "--record-interval" => {
+                let value = args.next().expect("--record-interval requires a step count");
+                record_interval =
+                    value.parse().unwrap_or_else(|_| panic!("Invalid step count '{}'", value));
+            }
+            "--export" => export_directory = Some(args.next().expect("--export requires a directory")),
+            "--export-format" => {
+                let name = args.next().expect("--export-format requires csv, vtk, vtp, ply, npy or npz");
+                export_format = ExportFormat::from_name(&name)
+                    .unwrap_or_else(|| panic!("Unknown export format '{}'", name));
+            }
+            "--export-interval" => {
+                let value = args.next().expect("--export-interval requires a step count");
+                export_interval =
+                    value.parse().unwrap_or_else(|_| panic!("Invalid step count '{}'", value));
+            }
+            "--export-grid" => {
+                let value = args.next().expect("--export-grid requires <columns>x<rows>");
+                export_grid = Some(
+                    GridSpec::from_str_world(&value)
+                        .unwrap_or_else(|| panic!("Invalid grid size '{}'", value)),
+                );
+            }
+            "--probe" => {
+                let spec = args.next().expect("--probe requires <name>:<point|line|box>:<x,y>[:<x,y>]");
+                Probe::from_spec(&spec).unwrap_or_else(|| panic!("Invalid probe '{}'", spec));
+                probe_specs.push(spec);
+            }
+            "--probes-csv" => probes_csv_path = Some(args.next().expect("--probes-csv requires a path")),
+            "--diagnostics" => diagnostics_path = Some(args.next().expect("--diagnostics requires a path")),
+            "--trace" => trace_path = Some(args.next().expect("--trace requires a path")),
+            "--threads" => {
+                let value = args.next().expect("--threads requires a count");
+                thread_count =
+                    Some(value.parse().unwrap_or_else(|_| panic!("Invalid thread count '{}'", value)));
+            }
+            "--replay" => replay_path = Some(args.next().expect("--replay requires a path")),
+            _ => panic!("Unknown argument '{}'", arg),
+        }
+    }
+
+    // Physics runs on rayon's global pool, which uses every core unless told otherwise
+    if let Some(thread_count) = thread_count {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .build_global()
+            .expect("Failed to set up the physics thread pool");
+    }
+
+    let mut profiler = Profiler::default();
+    if let Some(path) = &trace_path {
+        profiler = profiler
+            .with_trace(path)
+            .unwrap_or_else(|e| panic!("Failed to create trace '{}': {}", path, e));
+    }
+
+    // 3D scenes have their own viewer, without the 2D-only features below
+    if let Some(scene_3d) = scene_3d {
+        let unsupported = [
+            ("--load", load_path.is_some()),
+            ("--replay", replay_path.is_some()),
+            ("--record", record_path.is_some()),
+            ("--export", export_directory.is_some()),
+            ("--probe", !probe_specs.is_empty()),
+            ("--diagnostics", diagnostics_path.is_some()),
+        ];
+        for (flag, given) in unsupported {
+            if given {
+                panic!("{} is not supported for 3D scenes", flag);
+            }
+        }
+        match headless_steps {
+            Some(steps) => {
+                viewer_3d::run_headless(scene_3d, seed, steps, &mut profiler);
+                println!("Stage times in ms (min, mean, max):\n{}", Profiler::summary(profiler.totals()));
+            }
+            None => viewer_3d::run_viewer(scene_3d, seed, &key_bindings, &mut profiler),
+        }
+        profiler.finish().expect("Failed to write trace");
+        return;
+    }
+
+    let mut scene_data = match &load_path {
+        Some(path) => {
+            let (loaded_scene, loaded_data) = Snapshot::load(path)
+                .and_then(Snapshot::restore)
+                .unwrap_or_else(|e| panic!("Failed to load snapshot '{}': {}", path, e));
+            scene = loaded_scene;
+            loaded_data
+        }
+        None => scene.build(seed),
+    };
+    add_probes(&mut scene_data, &probe_specs);
+
+    // Replays draw recorded frames over the scene they were recorded from instead of running physics
+    let replay = replay_path.map(|path| {
+        let trajectory = Trajectory::load(&path)
+            .unwrap_or_else(|e| panic!("Failed to load trajectory '{}': {}", path, e));
+        scene = trajectory
+            .scene()
+            .unwrap_or_else(|e| panic!("Failed to load trajectory '{}': {}", path, e));
+        scene_data = scene.build(seed);
+        trajectory
+    });
+    let mut replay_time = replay.as_ref().map_or(0.0, |t| t.start_time());
+
+    let mut recorder = record_path.as_ref().map(|path| {
+        TrajectoryRecorder::create(path, scene, record_interval)
+            .unwrap_or_else(|e| panic!("Failed to create trajectory '{}': {}", path, e))
+    });
+
+    let mut diagnostics_log = diagnostics_path.as_ref().map(|path| {
+        DiagnosticsLog::create(path)
+            .unwrap_or_else(|e| panic!("Failed to create diagnostics log '{}': {}", path, e))
+    });
+
+    let mut probe_log = probes_csv_path.as_ref().map(|path| {
+        ProbeLog::create(path, &scene_data)
+            .unwrap_or_else(|e| panic!("Failed to create probe log '{}': {}", path, e))
+    });
+
+    let delta_time = 1.0 / 100.0;
+
+    let mut exporter = export_directory.map(|directory| {
+        let mut exporter = ParticleExporter::new(export_format, &directory, export_interval);
+        if let Some(spec) = export_grid {
+            exporter = exporter.with_grid(spec);
+        }
+        exporter
+            .write_metadata(scene.name(), seed, delta_time, &scene_data.config)
+            .unwrap_or_else(|e| panic!("Failed to write export metadata to '{}': {}", directory, e));
+        exporter
+    });
+
+    // Runs a fixed number of steps without a window and saves the result
+    if let Some(steps) = headless_steps {
+        for _ in 0..steps {
+            physics_update_profiled(&mut scene_data, delta_time, &CursorState::None, &mut profiler);
+            if let Some(recorder) = &mut recorder {
+                recorder.record(&scene_data).expect("Failed to record trajectory");
+            }
+            if let Some(exporter) = &mut exporter {
+                exporter.export_step(&scene_data).expect("Failed to export particles");
+            }
+            if let Some(log) = &mut diagnostics_log {
+                log.record(&Diagnostics::compute(&scene_data)).expect("Failed to log diagnostics");
+            }
+            if let Some(log) = &mut probe_log {
+                log.record(&scene_data).expect("Failed to log probes");
+            }
+        }
+        if let Some(recorder) = recorder {
+            recorder.finish().expect("Failed to record trajectory");
+        }
+        if let Some(log) = diagnostics_log {
+            log.finish().expect("Failed to log diagnostics");
+        }
+        if let Some(log) = probe_log {
+            log.finish().expect("Failed to log probes");
+        }
+        Snapshot::capture(scene, &scene_data)
+            .save(&snapshot_path)
+            .unwrap_or_else(|e| panic!("Failed to save snapshot '{}': {}", snapshot_path, e));
+        println!("Saved {} after {} steps", snapshot_path, steps);
+        println!("Stage times in ms (min, mean, max):\n{}", Profiler::summary(profiler.totals()));
+        profiler.finish().expect("Failed to write trace");
+        return;
+    }
+
     let mut sdl2_data = init_sdl2();
-    let mut scene_data = SceneData::<PARTICLE_COUNT>::new(SpawningMethod::Random);
     let mut fps_manager = FPSManager::new();
     fps_manager.set_framerate(TARGET_FPS).unwrap();
 
-    let delta_time = 1.0 / TARGET_FPS as Fp;
-    let delta_time = 1.0 / 100.0;
-
     let mut prev_tick = sdl2_data.timer.performance_counter();
     let tick_freq = sdl2_data.timer.performance_frequency();
 
     let mut frame: u128 = 0;
 
+    let mut colour_mode = ColourMode::Velocity;
+
+    let mut cursor_tool = CursorTool::default();
+    let mut overlays = Overlays::default();
+    let mut paused = false;
+    let mut time_scale: Fp = 1.0;
+    let mut prev_cursor_pos: Option<Vector2<Fp>> = None;
+    let heatmap_sampler =
+        GridSampler::new(GridSpec::world(HEATMAP_RESOLUTION, HEATMAP_RESOLUTION));
+
     unsafe {
         gl::Viewport(0, 0, SCREEN_WIDTH as GLsizei, SCREEN_HEIGHT as GLsizei);
         gl::ClearColor(0.0, 0.0, 0.0, 1.0);
     }
 
     'main_loop: loop {
+        let mut step_requested = false;
+        let mut step_back_requested = false;
+        let mut recorder_failed = false;
+        let mut exporter_failed = false;
+        let mut diagnostics_failed = false;
+        let mut probe_log_failed = false;
         for event in sdl2_data.event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::P),
+            let action = match event {
+                Event::Quit { .. } => Some(Action::Quit),
+                Event::KeyDown {
+                    keycode: Some(keycode),
                     ..
-                } => break 'main_loop,
-                _ => {}
+                } => key_bindings.action(keycode),
+                Event::MouseWheel { y, .. } => {
+                    cursor_tool.radius = (cursor_tool.radius * CURSOR_SCROLL_SCALE.powi(y))
+                        .clamp(INTERACTION_RADIUS, WORLD_HEIGHT);
+                    None
+                }
+                _ => None,
+            };
+
+            match action {
+                Some(Action::Quit) => break 'main_loop,
+                Some(Action::Pause) => paused = !paused,
+                Some(Action::Step) => step_requested = true,
+                Some(Action::StepBack) => step_back_requested = true,
+                Some(Action::Reset) => match &replay {
+                    Some(trajectory) => replay_time = trajectory.start_time(),
+                    None => {
+                        scene_data = scene.build(seed);
+                        add_probes(&mut scene_data, &probe_specs);
+                    }
+                },
+                Some(Action::SaveSnapshot) => {
+                    match Snapshot::capture(scene, &scene_data).save(&snapshot_path) {
+                        Ok(()) => println!("Saved {}", snapshot_path),
+                        Err(e) => println!("Failed to save snapshot '{}': {}", snapshot_path, e),
+                    }
+                }
+                Some(Action::LoadSnapshot) => {
+                    match Snapshot::load(&snapshot_path).and_then(Snapshot::restore) {
+                        Ok((loaded_scene, loaded_data)) => {
+                            scene = loaded_scene;
+                            scene_data = loaded_data;
+                            add_probes(&mut scene_data, &probe_specs);
+                        }
+                        Err(e) => println!("Failed to load snapshot '{}': {}", snapshot_path, e),
+                    }
+                }
+                Some(Action::SlowDown) => {
+                    time_scale = (time_scale / TIME_SCALE_STEP).max(MIN_TIME_SCALE)
+                }
+                Some(Action::SpeedUp) => {
+                    time_scale = (time_scale * TIME_SCALE_STEP).min(MAX_TIME_SCALE)
+                }
+                Some(Action::ToggleColourMode) => {
+                    colour_mode = match colour_mode {
+                        ColourMode::Velocity => ColourMode::Vorticity,
+                        ColourMode::Vorticity => ColourMode::Velocity,
+                    }
+                }
+                Some(Action::ToggleEmitters) => scene_data.toggle_emitters(),
+                Some(Action::ToggleDrains) => scene_data.toggle_drains(),
+                Some(Action::ToggleOverlay(overlay)) => overlays.toggle(overlay),
+                Some(Action::SelectTool(tool)) => cursor_tool.tool = tool,
+                Some(Action::CycleFalloff) => cursor_tool.falloff = cursor_tool.falloff.next(),
+                Some(Action::CyclePaintMaterial) => {
+                    cursor_tool.material = match cursor_tool.material {
+                        Material::Water => Material::Ice,
+                        Material::Ice => Material::Steam,
+                        Material::Steam => Material::Water,
+                    }
+                }
+                Some(Action::IncreaseStrength) => cursor_tool.strength *= CURSOR_STRENGTH_STEP,
+                Some(Action::DecreaseStrength) => cursor_tool.strength /= CURSOR_STRENGTH_STEP,
+                // Handled below while held
+                Some(Action::TiltLeft) | Some(Action::TiltRight) | None => {}
             }
         }
 
-        let cursor_state = if sdl2_data.event_pump.mouse_state().left() {
-            CursorState::Push(screen_to_world((
-                sdl2_data.event_pump.mouse_state().x() as u32,
-                sdl2_data.event_pump.mouse_state().y() as u32,
-            )))
-        } else if sdl2_data.event_pump.mouse_state().right() {
-            CursorState::Pull(screen_to_world((
-                sdl2_data.event_pump.mouse_state().x() as u32,
-                sdl2_data.event_pump.mouse_state().y() as u32,
-            )))
+        let tick = sdl2_data.timer.performance_counter();
+        let true_delta_time = (tick - prev_tick) as Fp / tick_freq as Fp;
+        prev_tick = tick;
+
+        let mouse_state = sdl2_data.event_pump.mouse_state();
+        let cursor_pos = screen_to_world((mouse_state.x() as u32, mouse_state.y() as u32));
+        let cursor_vel = match prev_cursor_pos {
+            Some(prev) if true_delta_time > 0.0 => (cursor_pos - prev) / true_delta_time,
+            _ => Vector2::new(0.0, 0.0),
+        };
+        prev_cursor_pos = Some(cursor_pos);
+
+        // Left mouse button uses the tool, right mouse button uses it in reverse
+        let cursor_state = if replay.is_none() && (mouse_state.left() || mouse_state.right()) {
+            CursorState::Active {
+                pos: cursor_pos,
+                vel: cursor_vel,
+                reverse: !mouse_state.left(),
+                tool: cursor_tool,
+            }
         } else {
             CursorState::None
         };
 
-        let tick = sdl2_data.timer.performance_counter();
-        let true_delta_time = (tick - prev_tick) as Fp / tick_freq as Fp;
-        prev_tick = tick;
+        // Tilt the tank with the arrow keys or point gravity at the cursor with the middle mouse button
+        let keyboard_state = sdl2_data.event_pump.keyboard_state();
+        let held = |action| {
+            key_bindings
+                .keys_for(action)
+                .filter_map(Scancode::from_keycode)
+                .any(|scancode| keyboard_state.is_scancode_pressed(scancode))
+        };
+        if held(Action::TiltLeft) {
+            scene_data.config.gravity.rotate(-GRAVITY_TILT_SPEED * true_delta_time);
+        }
+        if held(Action::TiltRight) {
+            scene_data.config.gravity.rotate(GRAVITY_TILT_SPEED * true_delta_time);
+        }
+        if mouse_state.middle() {
+            let centre = Vector2::new(WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0);
+            scene_data.config.gravity.point_towards(cursor_pos - centre);
+        }
 
-        if USE_TRUE_DELTA_TIME {
-            physics_update(&mut scene_data, true_delta_time, &cursor_state);
+        if let Some(trajectory) = &replay {
+            // Hold the left mouse button and move across the window to scrub through the recording
+            let (start, end) = (trajectory.start_time(), trajectory.end_time());
+            let index = trajectory.frame_index_at(replay_time);
+            if mouse_state.left() {
+                replay_time = start + (cursor_pos.x / WORLD_WIDTH).clamp(0.0, 1.0) * (end - start);
+            } else if step_requested {
+                let next = (index + 1).min(trajectory.frames.len().saturating_sub(1));
+                replay_time = trajectory.frames.get(next).map_or(start, |f| f.time);
+            } else if step_back_requested {
+                replay_time = trajectory.frames.get(index.saturating_sub(1)).map_or(start, |f| f.time);
+            } else if !paused {
+                replay_time = (replay_time + true_delta_time * time_scale).min(end);
+            }
+
+            if let Some(frame) = trajectory.frames.get(trajectory.frame_index_at(replay_time)) {
+                frame.apply_to(&mut scene_data);
+            }
+        } else {
+            // Single steps use the fixed time step so they're the same size however long the pause
+            let step_time = if !paused {
+                Some(if USE_TRUE_DELTA_TIME { true_delta_time } else { delta_time })
+            } else if step_requested {
+                Some(delta_time)
+            } else {
+                None
+            };
+            if let Some(step_time) = step_time {
+                physics_update_profiled(&mut scene_data, step_time * time_scale, &cursor_state, &mut profiler);
+                if let Some(recorder) = &mut recorder {
+                    if let Err(e) = recorder.record(&scene_data) {
+                        println!("Stopped recording: {}", e);
+                        recorder_failed = true;
+                    }
+                }
+                if let Some(exporter) = &mut exporter {
+                    if let Err(e) = exporter.export_step(&scene_data) {
+                        println!("Stopped exporting: {}", e);
+                        exporter_failed = true;
+                    }
+                }
+                if let Some(log) = &mut diagnostics_log {
+                    if let Err(e) = log.record(&Diagnostics::compute(&scene_data)) {
+                        println!("Stopped logging diagnostics: {}", e);
+                        diagnostics_failed = true;
+                    }
+                }
+                if let Some(log) = &mut probe_log {
+                    if let Err(e) = log.record(&scene_data) {
+                        println!("Stopped logging probes: {}", e);
+                        probe_log_failed = true;
+                    }
+                }
+            }
+        }
+        if recorder_failed {
+            recorder = None;
+        }
+        if exporter_failed {
+            exporter = None;
+        }
+        if diagnostics_failed {
+            diagnostics_log = None;
         }
-        else {
-            physics_update(&mut scene_data, delta_time, &cursor_state);
+        if probe_log_failed {
+            probe_log = None;
         }
 
         // render_scene_data(&scene_data, &mut sdl2_data);
@@ -107,8 +499,54 @@ fn main() {
         // sdl2_data.canvas.present();
 
 
+        let render_start = Instant::now();
         // Initialise vertices for triangle
-        let mut vertices: Vec<f32> = Vec::with_capacity(6 * PARTICLE_COUNT);
+        let mut vertices: Vec<f32> = Vec::with_capacity(6 * 3 * scene_data.particles.len());
+
+        if overlays.heatmap {
+            let fields = heatmap_sampler.sample(&scene_data);
+            let cell = fields.spec.cell_size();
+            for row in 0..fields.spec.rows {
+                for column in 0..fields.spec.columns {
+                    let i = fields.index(column, row);
+                    let colour = match colour_mode {
+                        ColourMode::Velocity => {
+                            let density = single((fields.density[i] / REST_DENSITY).clamp(0.0, 1.5) / 1.5);
+                            (density * 0.2, density * 0.4, density * 0.6)
+                        }
+                        ColourMode::Vorticity => {
+                            let curl = single((fields.vorticity[i] / VORTICITY_COLOUR_SCALE).clamp(-1.0, 1.0));
+                            (curl.max(0.0) * 0.6, 0.0, (-curl).max(0.0) * 0.6)
+                        }
+                    };
+                    let min = fields.spec.point(column, row) - cell / 2.0;
+                    let corners = [
+                        min,
+                        min + Vector2::new(cell.x, 0.0),
+                        min + cell,
+                        min + Vector2::new(0.0, cell.y),
+                    ];
+                    for i in [0, 1, 2, 0, 2, 3] {
+                        push_vertex(&mut vertices, corners[i], colour);
+                    }
+                }
+            }
+        }
+
+        if overlays.sources {
+            for drain in &scene_data.drains {
+                let colour = if drain.enabled { (0.3, 0.3, 0.3) } else { (0.1, 0.1, 0.1) };
+                let corners = [
+                    drain.min,
+                    Vector2::new(drain.max.x, drain.min.y),
+                    drain.max,
+                    Vector2::new(drain.min.x, drain.max.y),
+                ];
+                for i in [0, 1, 2, 0, 2, 3] {
+                    push_vertex(&mut vertices, corners[i], colour);
+                }
+            }
+        }
 
         for particle in &scene_data.particles {
             let mut vel = particle.vel.magnitude();
@@ -116,9 +554,20 @@ fn main() {
                 vel = 0.6
             }
 
-            let red = (vel * (1.0 / 0.6)).sqrt();
+            let red = single((vel * (1.0 / 0.6)).sqrt());
             // let red = red * red;
 
+            let colour = match (&colour_mode, particle.material) {
+                (ColourMode::Vorticity, _) => {
+                    // Red for anticlockwise, blue for clockwise
+                    let curl = single((particle.vorticity / VORTICITY_COLOUR_SCALE).clamp(-1.0, 1.0));
+                    (curl.max(0.0), 0.0, (-curl).max(0.0))
+                }
+                (ColourMode::Velocity, Material::Water) => (red, 0.0, 1.0 - red),
+                (ColourMode::Velocity, Material::Ice) => (0.8, 0.9, 1.0),
+                (ColourMode::Velocity, Material::Steam) => (0.5, 0.5, 0.5),
+            };
+
             let vel = vel.clamp(0.0, 0.5);
             let mut first = true;
             let triangles = if vel > 0.2 {
@@ -135,20 +584,152 @@ fn main() {
                     first = false;
                 }
                 else {
-                    offset = offset * 0.6;
+                    offset *= 0.6;
                 }
-                let pos = world_to_open_gl(Vector2::new( particle.pos.x + offset.x, particle.pos.y + offset.y));
-                vertices.push(pos.x);
-                vertices.push(pos.y);
-                vertices.push(0.0);
+                push_vertex(&mut vertices, particle.pos + offset, colour);
+            }
+        }
 
-                vertices.push(red);
-                vertices.push(0.0);
-                vertices.push(1.0 - red);
+        if overlays.boundaries {
+            for boundary in &scene_data.boundaries {
+                let (start, end) = boundary.end_points(scene_data.time);
+                push_segment(&mut vertices, start, end, (0.8, 0.8, 0.8));
+            }
+
+            if !matches!(scene_data.tank_motion, Motion::Static) {
+                let offset = scene_data.tank_motion.state(scene_data.time).offset;
+                let corners = [
+                    Vector2::new(0.0, 0.0),
+                    Vector2::new(WORLD_WIDTH, 0.0),
+                    Vector2::new(WORLD_WIDTH, WORLD_HEIGHT),
+                    Vector2::new(0.0, WORLD_HEIGHT),
+                ];
+                for i in 0..4 {
+                    let (start, end) = (corners[i] + offset, corners[(i + 1) % 4] + offset);
+                    push_segment(&mut vertices, start, end, (0.8, 0.8, 0.8));
+                }
+            }
+
+            // Dashed line up the axis of axisymmetric scenes
+            if scene_data.geometry == Geometry::Axisymmetric {
+                for i in (0..20).step_by(2) {
+                    let (start, end) = (i as Fp * WORLD_HEIGHT / 20.0, (i + 1) as Fp * WORLD_HEIGHT / 20.0);
+                    push_segment(&mut vertices, Vector2::new(0.0, start), Vector2::new(0.0, end), (0.5, 0.5, 0.8));
+                }
             }
         }
 
+        if overlays.gravity {
+            match &scene_data.config.gravity {
+                Gravity::Uniform(accel) if accel.magnitude2() > 0.0 => {
+                    // Arrow in the top left corner showing the direction of gravity
+                    let start = Vector2::new(0.08, WORLD_HEIGHT - 0.08);
+                    let end = start + accel.normalize() * 0.05;
+                    push_segment(&mut vertices, start, end, (1.0, 1.0, 0.0));
+                    for offset in generate_triangle(accel.normalize() / 80.0) {
+                        push_vertex(&mut vertices, end + offset, (1.0, 1.0, 0.0));
+                    }
+                }
+                Gravity::Sources(sources) => {
+                    for source in sources {
+                        for offset in generate_triangle(Vector2::new(0.0, 0.015)) {
+                            push_vertex(&mut vertices, source.pos + offset, (1.0, 1.0, 0.0));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if overlays.sources {
+            for emitter in &scene_data.emitters {
+                let colour = if emitter.enabled { (0.0, 1.0, 0.0) } else { (0.2, 0.4, 0.2) };
+                for offset in generate_triangle(emitter.direction / 25.0) {
+                    push_vertex(&mut vertices, emitter.pos + offset, colour);
+                }
+            }
+        }
+
+        if overlays.cursor_outline {
+            // Outline of the tool's reach
+            const CURSOR_OUTLINE_SEGMENTS: usize = 48;
+            for i in 0..CURSOR_OUTLINE_SEGMENTS {
+                let angle = |i: usize| i as Fp / CURSOR_OUTLINE_SEGMENTS as Fp * 2.0 * Fp::PI();
+                let point = |angle: Fp| cursor_pos + Vector2::new(angle.cos(), angle.sin()) * cursor_tool.radius;
+                push_segment(&mut vertices, point(angle(i)), point(angle(i + 1)), (0.4, 0.4, 0.4));
+            }
+        }
 
+        if overlays.hud {
+            let status = format!("{}Speed: {}x", if paused { "Paused\n" } else { "" }, time_scale);
+            let hud_text = match &replay {
+                Some(trajectory) => {
+                    // Timeline along the bottom with a marker at the current frame
+                    let (start, end) = (trajectory.start_time(), trajectory.end_time());
+                    let progress = if end > start { (replay_time - start) / (end - start) } else { 0.0 };
+                    let y = 0.01;
+                    push_segment(&mut vertices, Vector2::new(0.0, y), Vector2::new(WORLD_WIDTH, y), (0.4, 0.4, 0.4));
+                    let marker = Vector2::new(progress * WORLD_WIDTH, y);
+                    push_segment(&mut vertices, marker - Vector2::new(0.0, 0.01), marker + Vector2::new(0.0, 0.01), (1.0, 1.0, 1.0));
+
+                    format!(
+                        "{}\nReplay: {:.2} / {:.2}s\nFrame: {} / {}",
+                        status,
+                        replay_time,
+                        end,
+                        trajectory.frame_index_at(replay_time) + 1,
+                        trajectory.frames.len(),
+                    )
+                }
+                None => {
+                    let mut text = format!(
+                        "{}\nTool: {}\nRadius: {:.2}\nStrength: {:.1}\nFalloff: {}\nPaint: {:?}",
+                        status,
+                        cursor_tool.tool.name(),
+                        cursor_tool.radius,
+                        cursor_tool.strength,
+                        cursor_tool.falloff.name(),
+                        cursor_tool.material,
+                    );
+                    if overlays.diagnostics {
+                        let d = Diagnostics::compute(&scene_data);
+                        text += &format!(
+                            "\n\nKE: {:.3}\nPE: {:.3}\nE: {:.3}\nPx: {:.3}\nPy: {:.3}\nL: {:.4}\nRho err: {:.1}%\nRho max: {:.1}%\nMax vel: {:.2}",
+                            d.kinetic_energy,
+                            d.potential_energy,
+                            d.total_energy(),
+                            d.momentum.x,
+                            d.momentum.y,
+                            d.angular_momentum,
+                            d.mean_density_error * 100.0,
+                            d.max_density_error * 100.0,
+                            d.max_speed,
+                        );
+                    }
+                    text
+                }
+            };
+            let hud_origin = Vector2::new(WORLD_WIDTH - 0.34, WORLD_HEIGHT - 0.02);
+            for (min, max) in text_quads(&hud_text, hud_origin, 0.002) {
+                let corners = [min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)];
+                for i in [0, 1, 2, 0, 2, 3] {
+                    push_vertex(&mut vertices, corners[i], (1.0, 1.0, 1.0));
+                }
+            }
+        }
+
+        if overlays.profiler {
+            let text = format!("Stage ms: min mean max\n{}", Profiler::summary(profiler.last_window()));
+            for (min, max) in text_quads(&text, Vector2::new(0.02, WORLD_HEIGHT - 0.2), 0.0015) {
+                let corners = [min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)];
+                for i in [0, 1, 2, 0, 2, 3] {
+                    push_vertex(&mut vertices, corners[i], (1.0, 1.0, 0.6));
+                }
+            }
+        }
+        profiler.record("build_vertices", render_start);
+
+        let draw_start = Instant::now();
         let mut vbo: gl::types::GLuint = 0;
         unsafe {
             gl::GenBuffers(1, &mut vbo); // Request 1 buffer, put buffer name into vbo
@@ -209,15 +790,65 @@ fn main() {
         }
 
         sdl2_data.renderer.window().gl_swap_window();
+        profiler.record("draw", draw_start);
+        profiler.tick();
 
         if USE_SDL2_DELAY {
             fps_manager.delay();
         }
 
-        if frame % TARGET_FPS as u128 == 0 {
+        if frame.is_multiple_of(TARGET_FPS as u128) {
             println!("{} fps", 1.0 / true_delta_time);
         }
 
         frame += 1;
     }
+
+    if let Some(recorder) = recorder {
+        recorder.finish().unwrap_or_else(|e| println!("Failed to finish trajectory: {}", e));
+    }
+    if let Some(log) = diagnostics_log {
+        log.finish().unwrap_or_else(|e| println!("Failed to finish diagnostics log: {}", e));
+    }
+    profiler.finish().unwrap_or_else(|e| println!("Failed to finish trace: {}", e));
+    if let Some(log) = probe_log {
+        log.finish().unwrap_or_else(|e| println!("Failed to finish probe log: {}", e));
+    }
+}
+
+/// Adds the probes given with --probe, which are checked when the arguments are parsed
+fn add_probes(scene_data: &mut SceneData, specs: &[String]) {
+    scene_data
+        .probes
+        .extend(specs.iter().filter_map(|spec| Probe::from_spec(spec)));
+}
+
+/// Narrows a simulation value for a vertex colour
+#[allow(clippy::unnecessary_cast)] // Only unnecessary without the f64 feature
+fn single(value: Fp) -> f32 {
+    value as f32
+}
+
+/// Vertices are always single precision, whatever precision the simulation runs at
+#[allow(clippy::unnecessary_cast)] // Only unnecessary without the f64 feature
+fn push_vertex(vertices: &mut Vec<f32>, world_pos: Vector2<Fp>, colour: (f32, f32, f32)) {
+    let pos = world_to_open_gl(world_pos);
+    vertices.push(pos.x as f32);
+    vertices.push(pos.y as f32);
+    vertices.push(0.0);
+
+    vertices.push(colour.0);
+    vertices.push(colour.1);
+    vertices.push(colour.2);
+}
+
+/// Pushes a thin quad covering the line from `start` to `end`
+fn push_segment(vertices: &mut Vec<f32>, start: Vector2<Fp>, end: Vector2<Fp>, colour: (f32, f32, f32)) {
+    const HALF_WIDTH: Fp = 0.004;
+    let direction = (end - start).normalize();
+    let across = Vector2::new(-direction.y, direction.x) * HALF_WIDTH;
+    let corners = [start - across, end - across, end + across, start + across];
+    for i in [0, 1, 2, 0, 2, 3] {
+        push_vertex(vertices, corners[i], colour);
+    }
 }
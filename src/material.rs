@@ -0,0 +1,53 @@
+use crate::thermal::AMBIENT_TEMPERATURE;
+use crate::Fp;
+use serde::{Deserialize, Serialize};
+
+// Temperatures are in degrees Celsius
+pub const FREEZING_POINT: Fp = 0.0;
+pub const MELTING_POINT: Fp = 2.0;
+pub const BOILING_POINT: Fp = 100.0;
+pub const CONDENSATION_POINT: Fp = 95.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Material {
+    Water,
+    Ice,
+    Steam,
+}
+
+impl Material {
+    /// Returns the material a particle of this material should become at `temperature`.
+    /// Melting and condensation happen slightly away from freezing and boiling so particles
+    /// sitting on a threshold don't flicker between phases.
+    pub fn phase_at(&self, temperature: Fp) -> Material {
+        match self {
+            Material::Water if temperature < FREEZING_POINT => Material::Ice,
+            Material::Water if temperature > BOILING_POINT => Material::Steam,
+            Material::Ice if temperature > MELTING_POINT => Material::Water,
+            Material::Steam if temperature < CONDENSATION_POINT => Material::Water,
+            _ => *self,
+        }
+    }
+
+    /// Temperature new particles of this material start at, comfortably inside the phase
+    pub fn spawn_temperature(&self) -> Fp {
+        match self {
+            Material::Water => AMBIENT_TEMPERATURE,
+            Material::Ice => FREEZING_POINT - 10.0,
+            Material::Steam => BOILING_POINT + 10.0,
+        }
+    }
+
+    /// Multiplier applied to gravity, steam is buoyant so it rises
+    pub fn gravity_scale(&self) -> Fp {
+        match self {
+            Material::Water => 1.0,
+            Material::Ice => 1.0,
+            Material::Steam => -0.3,
+        }
+    }
+
+    pub fn is_solid(&self) -> bool {
+        matches!(self, Material::Ice)
+    }
+}
@@ -1,6 +1,53 @@
-use crate::{Fp, SCREEN_HEIGHT, SCREEN_WIDTH, WORLD_HEIGHT, WORLD_WIDTH};
-use cgmath::{Vector2, Vector3, Rotation};
+use crate::{Fp, SCREEN_HEIGHT, WORLD_HEIGHT, WORLD_WIDTH};
 use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2, Vector3};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ops::{AddAssign, Index, IndexMut, Neg, SubAssign};
+
+/// A position, velocity or acceleration in a 2D or 3D world, so particle storage, the neighbour
+/// grid, the kernels and force fields are written once for both
+pub trait Vector:
+    InnerSpace<Scalar = Fp>
+    + AddAssign
+    + SubAssign
+    + Neg<Output = Self>
+    + Index<usize, Output = Fp>
+    + IndexMut<usize>
+    + Send
+    + Sync
+    + Serialize
+    + DeserializeOwned
+{
+    const DIMENSIONS: usize;
+    /// An array of values per axis, e.g. the positions in a `Particles`
+    type Columns: AsRef<[Vec<Fp>]> + AsMut<[Vec<Fp>]> + Clone + Default + Send + Sync;
+    /// A slice of values per axis
+    type Slices<'a>: AsRef<[&'a mut [Fp]]> + AsMut<[&'a mut [Fp]]> + Default;
+
+    /// The vector with `component(axis)` along each axis
+    fn from_fn(component: impl FnMut(usize) -> Fp) -> Self;
+}
+
+impl Vector for Vector2<Fp> {
+    const DIMENSIONS: usize = 2;
+    type Columns = [Vec<Fp>; 2];
+    type Slices<'a> = [&'a mut [Fp]; 2];
+
+    fn from_fn(mut component: impl FnMut(usize) -> Fp) -> Self {
+        Vector2::new(component(0), component(1))
+    }
+}
+
+impl Vector for Vector3<Fp> {
+    const DIMENSIONS: usize = 3;
+    type Columns = [Vec<Fp>; 3];
+    type Slices<'a> = [&'a mut [Fp]; 3];
+
+    fn from_fn(mut component: impl FnMut(usize) -> Fp) -> Self {
+        Vector3::new(component(0), component(1), component(2))
+    }
+}
 
 const WORLD_TO_SCREEN_SCALE_FACTOR: Fp = SCREEN_HEIGHT as Fp / WORLD_HEIGHT;
 
@@ -0,0 +1,236 @@
+use crate::math::Vector;
+use crate::particle::Particles;
+use crate::{Fp, WORLD_HEIGHT, WORLD_WIDTH};
+use cgmath::Vector2;
+use std::ops::Range;
+
+/// Uniform grid over the world used to find nearby particles without checking every pair, in 2D
+/// unless `V` is a 3D vector. Particle indices are kept sorted by cell, so each cell, and each run
+/// of cells along a row, is a contiguous range of `order`.
+pub struct NeighbourGrid<V: Vector = Vector2<Fp>> {
+    cell_size: Fp,
+    size: V,
+    /// Cells along each axis, with a single cell along the axes `V` doesn't have
+    counts: [usize; 3],
+    /// Size of the cells along each axis, `cell_size` unless a periodic axis isn't a whole number
+    /// of cells across, when they're stretched to fit
+    cell_sizes: [Fp; 3],
+    /// Where each cell's particles start in `order`, with the particle count at the end
+    cell_starts: Vec<usize>,
+    order: Vec<usize>,
+    particle_count: usize,
+    periodic: [bool; 3],
+}
+
+impl NeighbourGrid {
+    pub fn new(cell_size: Fp) -> Self {
+        NeighbourGrid::covering(cell_size, Vector2::new(WORLD_WIDTH, WORLD_HEIGHT))
+    }
+}
+
+impl<V: Vector> NeighbourGrid<V> {
+    /// A grid over a `size` box from the origin rather than the world, e.g. for a tank bigger
+    /// than the screen
+    pub fn covering(cell_size: Fp, size: V) -> Self {
+        let mut grid = NeighbourGrid {
+            cell_size,
+            size,
+            counts: [1; 3],
+            cell_sizes: [cell_size; 3],
+            cell_starts: Vec::new(),
+            order: Vec::new(),
+            particle_count: 0,
+            periodic: [false; 3],
+        };
+        grid.size_cells();
+        grid
+    }
+
+    /// Divides each axis into cells. The last cell of a bounded axis can be a sliver, but the
+    /// cells either side of a periodic seam have to be full sized, so a periodic axis that isn't
+    /// a whole number of cells across is split into fewer, slightly larger cells instead.
+    fn size_cells(&mut self) {
+        for axis in 0..V::DIMENSIONS {
+            let cells = self.size[axis] / self.cell_size;
+            let whole = (cells - cells.round()).abs() <= cells * 1e-4;
+            let count = match (self.periodic[axis], whole) {
+                (false, _) => cells.ceil(),
+                (true, true) => cells.round(),
+                (true, false) => cells.floor(),
+            };
+            self.counts[axis] = count.max(1.0) as usize;
+            self.cell_sizes[axis] = if self.periodic[axis] && !whole {
+                self.size[axis] / self.counts[axis] as Fp
+            } else {
+                self.cell_size
+            };
+        }
+        self.cell_starts.resize(self.counts.iter().product::<usize>() + 1, 0);
+    }
+
+    pub fn cell_size(&self) -> Fp {
+        self.cell_size
+    }
+
+    pub fn size(&self) -> V {
+        self.size
+    }
+
+    /// Number of particles the grid was last built from
+    pub fn particle_count(&self) -> usize {
+        self.particle_count
+    }
+
+    /// Re-sorts every particle by cell, keeping the allocations. Particles within a cell stay
+    /// in index order. Axes marked in `periodic` also search across the seam at the opposite edge
+    /// of the world.
+    pub fn rebuild(&mut self, particles: &Particles<V>, periodic: &[bool]) {
+        let periodic = std::array::from_fn(|axis| periodic.get(axis).copied().unwrap_or(false));
+        if periodic != self.periodic {
+            self.periodic = periodic;
+            self.size_cells();
+        }
+        self.particle_count = particles.len();
+        let cells: Vec<usize> = (0..particles.len())
+            .map(|i| {
+                let [x, y, z] = self.cell_of(particles.pos(i));
+                (z * self.counts[1] + y) * self.counts[0] + x
+            })
+            .collect();
+
+        // Counting sort: count each cell, turn the counts into starts, then place the particles
+        self.cell_starts.iter_mut().for_each(|s| *s = 0);
+        for &cell in &cells {
+            self.cell_starts[cell + 1] += 1;
+        }
+        for cell in 1..self.cell_starts.len() {
+            self.cell_starts[cell] += self.cell_starts[cell - 1];
+        }
+        let mut next = self.cell_starts.clone();
+        self.order.resize(particles.len(), 0);
+        for (i, &cell) in cells.iter().enumerate() {
+            self.order[next[cell]] = i;
+            next[cell] += 1;
+        }
+    }
+
+    /// Particle indices sorted by cell, which the ranges from `for_each_candidate_run` index into
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Calls `f` with the index of every particle in the cells surrounding `pos`.
+    /// Candidates may be up to two cells away so callers must still check the distance.
+    pub fn for_each_candidate(&self, pos: V, f: impl FnMut(usize)) {
+        self.for_each_candidate_within(pos, self.cell_size, f);
+    }
+
+    /// Like `for_each_candidate`, but searching enough rings of cells to find every particle
+    /// within `range` of `pos`, for interactions that reach further than a cell
+    pub fn for_each_candidate_within(&self, pos: V, range: Fp, mut f: impl FnMut(usize)) {
+        self.for_each_candidate_run(pos, range, |run| self.order[run].iter().for_each(|&j| f(j)));
+    }
+
+    /// Like `for_each_candidate_within`, but calling `f` with ranges of `order`, one per run of
+    /// neighbouring cells along a row, so callers can loop over contiguous arrays
+    pub fn for_each_candidate_run(&self, pos: V, range: Fp, mut f: impl FnMut(Range<usize>)) {
+        let reach: [usize; 3] =
+            std::array::from_fn(|axis| ((range / self.cell_sizes[axis]).ceil() as usize).max(1));
+        let [x, y, z] = self.cell_of(pos);
+        let [columns, rows, layers] = self.counts;
+        for cz in neighbouring_cells(z, layers, self.periodic[2], reach[2]) {
+            for cy in neighbouring_cells(y, rows, self.periodic[1], reach[1]) {
+                let row = (cz * rows + cy) * columns;
+                let mut run: Option<Range<usize>> = None;
+                for cx in neighbouring_cells(x, columns, self.periodic[0], reach[0]) {
+                    let cell = self.cell_starts[row + cx]..self.cell_starts[row + cx + 1];
+                    run = match run {
+                        Some(run) if run.end == cell.start => Some(run.start..cell.end),
+                        Some(run) => {
+                            f(run);
+                            Some(cell)
+                        }
+                        None => Some(cell),
+                    };
+                }
+                if let Some(run) = run {
+                    f(run);
+                }
+            }
+        }
+    }
+
+    /// Shortest vector from `from` to `to`, taking the wrap-around on periodic axes into account
+    pub fn displacement(&self, from: V, to: V) -> V {
+        let wrapping = self.wrapping();
+        let displacement = to - from;
+        V::from_fn(|axis| wrapping.apply(axis, displacement[axis]))
+    }
+
+    /// The wrap-around `displacement` applies, as a value to copy into hot loops
+    pub fn wrapping(&self) -> Wrapping {
+        Wrapping {
+            size: std::array::from_fn(|axis| if axis < V::DIMENSIONS { self.size[axis] } else { 0.0 }),
+            periodic: self.periodic,
+        }
+    }
+
+    fn cell_of(&self, pos: V) -> [usize; 3] {
+        // Particles outside the world wrap around periodic axes, as `displacement` does, and are
+        // clamped into the edge cells on the others
+        std::array::from_fn(|axis| {
+            if axis >= V::DIMENSIONS {
+                0
+            } else if self.periodic[axis] {
+                ((pos[axis] / self.cell_sizes[axis]).floor() as isize).rem_euclid(self.counts[axis] as isize) as usize
+            } else {
+                ((pos[axis] / self.cell_sizes[axis]).max(0.0) as usize).min(self.counts[axis] - 1)
+            }
+        })
+    }
+}
+
+/// Wraps displacements the way `NeighbourGrid::displacement` does
+#[derive(Clone, Copy)]
+pub struct Wrapping {
+    size: [Fp; 3],
+    periodic: [bool; 3],
+}
+
+impl Wrapping {
+    /// The shortest displacement for the component along `axis` of one
+    pub fn apply(&self, axis: usize, displacement: Fp) -> Fp {
+        if self.periodic[axis] {
+            wrap_displacement(displacement, self.size[axis])
+        } else {
+            displacement
+        }
+    }
+}
+
+/// Cells up to `reach` either side of `cell`, wrapping around on periodic axes. A periodic axis
+/// too few cells across to wrap without visiting a cell twice is searched end to end.
+fn neighbouring_cells(cell: usize, count: usize, periodic: bool, reach: usize) -> impl Iterator<Item = usize> {
+    let (start, end) = if periodic && count > 2 * reach {
+        (cell as isize - reach as isize, (cell + reach) as isize)
+    } else if periodic {
+        (0, count as isize - 1)
+    } else {
+        (
+            cell.saturating_sub(reach) as isize,
+            (cell + reach).min(count - 1) as isize,
+        )
+    };
+    (start..=end).map(move |c| c.rem_euclid(count as isize) as usize)
+}
+
+fn wrap_displacement(displacement: Fp, size: Fp) -> Fp {
+    // Two selects rather than an else-if, so loops over displacements vectorise. A displacement
+    // wrapped by the first is never caught by the second.
+    let displacement = if displacement > size / 2.0 { displacement - size } else { displacement };
+    if displacement < -size / 2.0 {
+        displacement + size
+    } else {
+        displacement
+    }
+}
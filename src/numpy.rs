@@ -0,0 +1,190 @@
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Types that can be stored in a `.npy` array
+pub trait NpyElement: Copy {
+    /// NumPy dtype string, e.g. `<f4`
+    const DESCR: &'static str;
+    fn write_le(&self, w: &mut impl Write) -> io::Result<()>;
+}
+
+macro_rules! impl_npy_element {
+    ($t:ty, $descr:expr) => {
+        impl NpyElement for $t {
+            const DESCR: &'static str = $descr;
+            fn write_le(&self, w: &mut impl Write) -> io::Result<()> {
+                w.write_all(&self.to_le_bytes())
+            }
+        }
+    };
+}
+
+impl_npy_element!(f32, "<f4");
+impl_npy_element!(f64, "<f8");
+impl_npy_element!(u8, "|u1");
+impl_npy_element!(i32, "<i4");
+impl_npy_element!(u32, "<u4");
+impl_npy_element!(u64, "<u8");
+
+/// Writes a C-ordered array in `.npy` format (version 1.0). `data` must hold the product of
+/// `shape` elements, an empty shape is a scalar.
+pub fn write_npy<T: NpyElement>(w: &mut impl Write, shape: &[usize], data: &[T]) -> io::Result<()> {
+    assert_eq!(shape.iter().product::<usize>(), data.len(), "shape doesn't match data length");
+
+    let shape_text = match shape {
+        [n] => format!("({},)", n),
+        _ => format!(
+            "({})",
+            shape.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR,
+        shape_text
+    );
+    // Magic, version and header length take 10 bytes, and the header ends in a newline
+    // with the total padded to a multiple of 64
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    w.write_all(b"\x93NUMPY\x01\x00")?;
+    w.write_all(&(header.len() as u16).to_le_bytes())?;
+    w.write_all(header.as_bytes())?;
+    for value in data {
+        value.write_le(w)?;
+    }
+    Ok(())
+}
+
+pub fn save_npy<T: NpyElement>(path: impl AsRef<Path>, shape: &[usize], data: &[T]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_npy(&mut writer, shape, data)?;
+    writer.flush()
+}
+
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Writes a `.npz` archive (a zip of `.npy` files) that `numpy.load` opens like a dict
+pub struct NpzWriter {
+    writer: BufWriter<File>,
+    entries: Vec<ZipEntry>,
+    offset: u64,
+    /// Largest size or offset the archive may record, zip fields are 32 bit without ZIP64
+    size_limit: u32,
+}
+
+impl NpzWriter {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(NpzWriter {
+            writer: BufWriter::new(File::create(path)?),
+            entries: Vec::new(),
+            offset: 0,
+            size_limit: u32::MAX,
+        })
+    }
+
+    /// Lowers the largest size or offset the archive may record, so overflowing it can be tested
+    /// without writing gigabytes
+    pub fn with_size_limit(mut self, size_limit: u32) -> Self {
+        self.size_limit = size_limit;
+        self
+    }
+
+    /// Converts a size or offset for a 32 bit zip field, failing rather than truncating it
+    fn zip_field(&self, value: u64, what: &str) -> io::Result<u32> {
+        if value > self.size_limit as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} of {} bytes is too large for a zip archive without ZIP64", what, value),
+            ));
+        }
+        Ok(value as u32)
+    }
+
+    /// Adds an array that will be loaded under `name`
+    pub fn add_array<T: NpyElement>(&mut self, name: &str, shape: &[usize], data: &[T]) -> io::Result<()> {
+        let mut npy = Vec::new();
+        write_npy(&mut npy, shape, data)?;
+        let mut crc = Crc::new();
+        crc.update(&npy);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&npy)?;
+        let compressed = encoder.finish()?;
+
+        let name = format!("{}.npy", name);
+        let name_length = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "array name is too long for a zip archive"))?;
+        let entry = ZipEntry {
+            crc: crc.sum(),
+            compressed_size: self.zip_field(compressed.len() as u64, "compressed array")?,
+            size: self.zip_field(npy.len() as u64, "array")?,
+            offset: self.zip_field(self.offset, "archive")?,
+            name,
+        };
+
+        let w = &mut self.writer;
+        w.write_all(&0x0403_4b50u32.to_le_bytes())?; // Local file header signature
+        w.write_all(&20u16.to_le_bytes())?; // Version needed to extract
+        w.write_all(&0u16.to_le_bytes())?; // Flags
+        w.write_all(&8u16.to_le_bytes())?; // Deflate
+        w.write_all(&0u32.to_le_bytes())?; // Modification time and date
+        w.write_all(&entry.crc.to_le_bytes())?;
+        w.write_all(&entry.compressed_size.to_le_bytes())?;
+        w.write_all(&entry.size.to_le_bytes())?;
+        w.write_all(&name_length.to_le_bytes())?;
+        w.write_all(&0u16.to_le_bytes())?; // Extra field length
+        w.write_all(entry.name.as_bytes())?;
+        w.write_all(&compressed)?;
+
+        self.offset += 30 + name_length as u64 + entry.compressed_size as u64;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Writes the zip central directory, the archive is unreadable until this is called
+    pub fn finish(mut self) -> io::Result<()> {
+        let entry_count = u16::try_from(self.entries.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many arrays for a zip archive"))?;
+        let directory_offset = self.zip_field(self.offset, "archive")?;
+        let directory_size = self.entries.iter().map(|e| 46 + e.name.len() as u64).sum();
+        let directory_size = self.zip_field(directory_size, "central directory")?;
+
+        let w = &mut self.writer;
+        for entry in &self.entries {
+            w.write_all(&0x0201_4b50u32.to_le_bytes())?; // Central directory header signature
+            w.write_all(&20u16.to_le_bytes())?; // Version made by
+            w.write_all(&20u16.to_le_bytes())?; // Version needed to extract
+            w.write_all(&0u16.to_le_bytes())?; // Flags
+            w.write_all(&8u16.to_le_bytes())?; // Deflate
+            w.write_all(&0u32.to_le_bytes())?; // Modification time and date
+            w.write_all(&entry.crc.to_le_bytes())?;
+            w.write_all(&entry.compressed_size.to_le_bytes())?;
+            w.write_all(&entry.size.to_le_bytes())?;
+            w.write_all(&(entry.name.len() as u16).to_le_bytes())?; // Checked in `add_array`
+            w.write_all(&[0; 8])?; // Extra field and comment lengths, disk number, internal attributes
+            w.write_all(&0u32.to_le_bytes())?; // External attributes
+            w.write_all(&entry.offset.to_le_bytes())?;
+            w.write_all(entry.name.as_bytes())?;
+        }
+
+        w.write_all(&0x0605_4b50u32.to_le_bytes())?; // End of central directory signature
+        w.write_all(&[0; 4])?; // Disk numbers
+        w.write_all(&entry_count.to_le_bytes())?;
+        w.write_all(&entry_count.to_le_bytes())?;
+        w.write_all(&directory_size.to_le_bytes())?;
+        w.write_all(&directory_offset.to_le_bytes())?;
+        w.write_all(&0u16.to_le_bytes())?; // Comment length
+        w.flush()
+    }
+}
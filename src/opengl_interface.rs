@@ -1,3 +1,4 @@
+use cgmath::Matrix4;
 use gl::types::GLuint;
 use std::ffi::{CStr, CString};
 use sdl2::render::WindowCanvas;
@@ -16,7 +17,7 @@ pub fn init_opengl(window: Window, video: &VideoSubsystem) -> (WindowCanvas, Sha
         .unwrap();
 
     renderer.window().gl_set_context_to_current().unwrap();
-    let _gl = gl::load_with(|s| video.gl_get_proc_address(s) as *const std::os::raw::c_void);
+    gl::load_with(|s| video.gl_get_proc_address(s) as *const std::os::raw::c_void);
 
     let vert_shader =
         Shader::from_vert_source(&CString::new(include_str!("triangle.vert")).unwrap())
@@ -33,6 +34,19 @@ pub fn init_opengl(window: Window, video: &VideoSubsystem) -> (WindowCanvas, Sha
     (renderer, shader_program)
 }
 
+/// Shader that draws points as shaded sprites in 3D, for the 3D viewer
+pub fn sprite_shader_program() -> ShaderProgram {
+    let vert_shader =
+        Shader::from_vert_source(&CString::new(include_str!("sprite.vert")).unwrap())
+            .unwrap();
+
+    let frag_shader =
+        Shader::from_frag_source(&CString::new(include_str!("sprite.frag")).unwrap())
+            .unwrap();
+
+    ShaderProgram::from_shaders(&[vert_shader, frag_shader]).unwrap()
+}
+
 pub fn find_sdl_gl_driver() -> Option<u32> {
     for (index, item) in sdl2::render::drivers().enumerate() {
         if item.name == "opengl" {
@@ -103,6 +117,31 @@ impl ShaderProgram {
             gl::UseProgram(self.id);
         }
     }
+
+    /// Uniforms are set on the program in use, so call `set_used` first
+    pub fn set_mat4(&self, name: &str, value: &Matrix4<f32>) {
+        let value: &[f32; 16] = value.as_ref();
+        unsafe {
+            gl::UniformMatrix4fv(self.uniform_location(name), 1, gl::FALSE, value.as_ptr());
+        }
+    }
+
+    pub fn set_float(&self, name: &str, value: f32) {
+        unsafe {
+            gl::Uniform1f(self.uniform_location(name), value);
+        }
+    }
+
+    pub fn set_bool(&self, name: &str, value: bool) {
+        unsafe {
+            gl::Uniform1i(self.uniform_location(name), value as gl::types::GLint);
+        }
+    }
+
+    fn uniform_location(&self, name: &str) -> gl::types::GLint {
+        let name = CString::new(name).unwrap();
+        unsafe { gl::GetUniformLocation(self.id, name.as_ptr()) }
+    }
 }
 
 impl Drop for ShaderProgram {
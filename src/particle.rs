@@ -1,20 +1,49 @@
+use crate::material::Material;
+use crate::math::Vector;
+use crate::thermal::AMBIENT_TEMPERATURE;
 use crate::Fp;
-use cgmath::{Vector2, Zero};
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+use std::ops::{Deref, DerefMut, Range};
 
-pub struct Particle {
-    pub pos: Vector2<Fp>,
-    pub vel: Vector2<Fp>,
-    pub accel: Vector2<Fp>,
+/// A single particle, in 2D unless `V` is a 3D vector
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "V: Vector")]
+pub struct Particle<V: Vector = Vector2<Fp>> {
+    pub pos: V,
+    pub vel: V,
+    pub accel: V,
     pub mass: Fp,
+    pub density: Fp,
+    /// Mechanical pressure from the repulsion the particle feels, see `apply_repulsive_particle_force`.
+    /// Not saved, the next step works it out again.
+    #[serde(skip)]
+    pub pressure: Fp,
+    pub vorticity: Fp,
+    pub temperature: Fp,
+    pub material: Material,
 }
 
-impl Particle {
-    pub fn new(pos: Vector2<Fp>, mass: Fp) -> Self {
+impl<V: Vector> Particle<V> {
+    pub fn new(pos: V, mass: Fp) -> Self {
         Particle {
             pos,
-            vel: Vector2::zero(),
-            accel: Vector2::zero(),
+            vel: V::zero(),
+            accel: V::zero(),
             mass,
+            density: 0.0,
+            pressure: 0.0,
+            vorticity: 0.0,
+            temperature: AMBIENT_TEMPERATURE,
+            material: Material::Water,
+        }
+    }
+
+    pub fn with_material(pos: V, mass: Fp, material: Material) -> Self {
+        Particle {
+            temperature: material.spawn_temperature(),
+            material,
+            ..Particle::new(pos, mass)
         }
     }
 
@@ -26,7 +55,391 @@ impl Particle {
         self.vel += self.accel * delta_time
     }
 
-    pub fn set_accel(&mut self, new_accel: Vector2<Fp>) {
+    pub fn set_accel(&mut self, new_accel: V) {
         self.accel = new_accel;
     }
 }
+
+/// Every particle in a scene, stored as one array per component (structure of arrays) so passes
+/// that only touch a few components stream through contiguous memory and vectorise. Positions,
+/// velocities and accelerations have an array per axis. Particles are read, added and iterated
+/// as `Particle` values, and changed through `ParticleMut` views from `get_mut` and `iter_mut`.
+/// Serialises as a list of `Particle`s, so files are the same as when particles were stored in a
+/// `Vec<Particle>`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<Particle<V>>", into = "Vec<Particle<V>>", bound = "V: Vector")]
+pub struct Particles<V: Vector = Vector2<Fp>> {
+    pub pos: V::Columns,
+    pub vel: V::Columns,
+    pub accel: V::Columns,
+    pub mass: Vec<Fp>,
+    pub density: Vec<Fp>,
+    pub pressure: Vec<Fp>,
+    pub vorticity: Vec<Fp>,
+    pub temperature: Vec<Fp>,
+    pub material: Vec<Material>,
+}
+
+impl<V: Vector> Default for Particles<V> {
+    fn default() -> Self {
+        Particles {
+            pos: V::Columns::default(),
+            vel: V::Columns::default(),
+            accel: V::Columns::default(),
+            mass: Vec::new(),
+            density: Vec::new(),
+            pressure: Vec::new(),
+            vorticity: Vec::new(),
+            temperature: Vec::new(),
+            material: Vec::new(),
+        }
+    }
+}
+
+impl<V: Vector> Particles<V> {
+    pub fn new() -> Self {
+        Particles::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mass.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mass.is_empty()
+    }
+
+    pub fn push(&mut self, particle: Particle<V>) {
+        for axis in 0..V::DIMENSIONS {
+            self.pos.as_mut()[axis].push(particle.pos[axis]);
+            self.vel.as_mut()[axis].push(particle.vel[axis]);
+            self.accel.as_mut()[axis].push(particle.accel[axis]);
+        }
+        self.mass.push(particle.mass);
+        self.density.push(particle.density);
+        self.pressure.push(particle.pressure);
+        self.vorticity.push(particle.vorticity);
+        self.temperature.push(particle.temperature);
+        self.material.push(particle.material);
+    }
+
+    /// A copy of the particle at `index`
+    pub fn get(&self, index: usize) -> Particle<V> {
+        Particle {
+            pos: self.pos(index),
+            vel: self.vel(index),
+            accel: self.accel(index),
+            mass: self.mass[index],
+            density: self.density[index],
+            pressure: self.pressure[index],
+            vorticity: self.vorticity[index],
+            temperature: self.temperature[index],
+            material: self.material[index],
+        }
+    }
+
+    /// Overwrites every component of the particle at `index`
+    pub fn set(&mut self, index: usize, particle: &Particle<V>) {
+        self.set_pos(index, particle.pos);
+        self.set_vel(index, particle.vel);
+        self.set_accel(index, particle.accel);
+        self.mass[index] = particle.mass;
+        self.density[index] = particle.density;
+        self.pressure[index] = particle.pressure;
+        self.vorticity[index] = particle.vorticity;
+        self.temperature[index] = particle.temperature;
+        self.material[index] = particle.material;
+    }
+
+    pub fn pos(&self, index: usize) -> V {
+        V::from_fn(|axis| self.pos.as_ref()[axis][index])
+    }
+
+    pub fn vel(&self, index: usize) -> V {
+        V::from_fn(|axis| self.vel.as_ref()[axis][index])
+    }
+
+    pub fn accel(&self, index: usize) -> V {
+        V::from_fn(|axis| self.accel.as_ref()[axis][index])
+    }
+
+    pub fn set_pos(&mut self, index: usize, pos: V) {
+        set_row(&mut self.pos, index, pos);
+    }
+
+    pub fn set_vel(&mut self, index: usize, vel: V) {
+        set_row(&mut self.vel, index, vel);
+    }
+
+    pub fn set_accel(&mut self, index: usize, accel: V) {
+        set_row(&mut self.accel, index, accel);
+    }
+
+    /// A view of the particle at `index` that writes any changes back when dropped
+    pub fn get_mut(&mut self, index: usize) -> ParticleMut<'_, V> {
+        let range = index..index + 1;
+        ParticleMut::new(ParticleSlices {
+            pos: slices::<V>(&mut self.pos, range.clone()),
+            vel: slices::<V>(&mut self.vel, range.clone()),
+            accel: slices::<V>(&mut self.accel, range.clone()),
+            mass: &mut self.mass[range.clone()],
+            density: &mut self.density[range.clone()],
+            pressure: &mut self.pressure[range.clone()],
+            vorticity: &mut self.vorticity[range.clone()],
+            temperature: &mut self.temperature[range.clone()],
+            material: &mut self.material[range],
+        })
+    }
+
+    pub fn iter(&self) -> ParticleIter<'_, V> {
+        ParticleIter {
+            particles: self,
+            range: 0..self.len(),
+        }
+    }
+
+    /// Iterates over views of the particles, see `get_mut`
+    pub fn iter_mut(&mut self) -> ParticleIterMut<'_, V> {
+        let all = 0..self.len();
+        ParticleIterMut {
+            slices: ParticleSlices {
+                pos: slices::<V>(&mut self.pos, all.clone()),
+                vel: slices::<V>(&mut self.vel, all.clone()),
+                accel: slices::<V>(&mut self.accel, all),
+                mass: &mut self.mass,
+                density: &mut self.density,
+                pressure: &mut self.pressure,
+                vorticity: &mut self.vorticity,
+                temperature: &mut self.temperature,
+                material: &mut self.material,
+            },
+        }
+    }
+
+    /// Keeps only the particles `keep` returns true for, preserving their order
+    pub fn retain(&mut self, mut keep: impl FnMut(&Particle<V>) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len() {
+            let particle = self.get(i);
+            if keep(&particle) {
+                if kept != i {
+                    self.set(kept, &particle);
+                }
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        for columns in [&mut self.pos, &mut self.vel, &mut self.accel] {
+            columns.as_mut().iter_mut().for_each(|column| column.truncate(len));
+        }
+        self.mass.truncate(len);
+        self.density.truncate(len);
+        self.pressure.truncate(len);
+        self.vorticity.truncate(len);
+        self.temperature.truncate(len);
+        self.material.truncate(len);
+    }
+}
+
+/// Writes `value` into row `index` of an array per axis
+fn set_row<V: Vector>(columns: &mut V::Columns, index: usize, value: V) {
+    for (axis, column) in columns.as_mut().iter_mut().enumerate() {
+        column[index] = value[axis];
+    }
+}
+
+/// The same `range` of each of an array per axis
+fn slices<V: Vector>(columns: &mut V::Columns, range: Range<usize>) -> V::Slices<'_> {
+    let mut slices = V::Slices::default();
+    for (slice, column) in slices.as_mut().iter_mut().zip(columns.as_mut()) {
+        *slice = &mut column[range.clone()];
+    }
+    slices
+}
+
+/// Iterates over copies of the particles in a `Particles`
+pub struct ParticleIter<'a, V: Vector = Vector2<Fp>> {
+    particles: &'a Particles<V>,
+    range: Range<usize>,
+}
+
+impl<V: Vector> Iterator for ParticleIter<'_, V> {
+    type Item = Particle<V>;
+
+    fn next(&mut self) -> Option<Particle<V>> {
+        self.range.next().map(|i| self.particles.get(i))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<V: Vector> DoubleEndedIterator for ParticleIter<'_, V> {
+    fn next_back(&mut self) -> Option<Particle<V>> {
+        self.range.next_back().map(|i| self.particles.get(i))
+    }
+}
+
+impl<V: Vector> ExactSizeIterator for ParticleIter<'_, V> {}
+
+impl<'a, V: Vector> IntoIterator for &'a Particles<V> {
+    type Item = Particle<V>;
+    type IntoIter = ParticleIter<'a, V>;
+
+    fn into_iter(self) -> ParticleIter<'a, V> {
+        self.iter()
+    }
+}
+
+/// The same run of particles in each component array of a `Particles`
+struct ParticleSlices<'a, V: Vector> {
+    pos: V::Slices<'a>,
+    vel: V::Slices<'a>,
+    accel: V::Slices<'a>,
+    mass: &'a mut [Fp],
+    density: &'a mut [Fp],
+    pressure: &'a mut [Fp],
+    vorticity: &'a mut [Fp],
+    temperature: &'a mut [Fp],
+    material: &'a mut [Material],
+}
+
+impl<'a, V: Vector> ParticleSlices<'a, V> {
+    /// Splits off the first particle, leaving the rest
+    fn split_first(&mut self) -> Option<ParticleSlices<'a, V>> {
+        fn split<'a, T>(slice: &mut &'a mut [T]) -> &'a mut [T] {
+            let (first, rest) = std::mem::take(slice).split_at_mut(1);
+            *slice = rest;
+            first
+        }
+        fn split_axes<'a, V: Vector>(slices: &mut V::Slices<'a>) -> V::Slices<'a> {
+            let mut first = V::Slices::default();
+            for (first, slice) in first.as_mut().iter_mut().zip(slices.as_mut()) {
+                *first = split(slice);
+            }
+            first
+        }
+        if self.mass.is_empty() {
+            return None;
+        }
+        Some(ParticleSlices {
+            pos: split_axes::<V>(&mut self.pos),
+            vel: split_axes::<V>(&mut self.vel),
+            accel: split_axes::<V>(&mut self.accel),
+            mass: split(&mut self.mass),
+            density: split(&mut self.density),
+            pressure: split(&mut self.pressure),
+            vorticity: split(&mut self.vorticity),
+            temperature: split(&mut self.temperature),
+            material: split(&mut self.material),
+        })
+    }
+}
+
+/// A copy of one particle that derefs to `Particle` and writes itself back into the arrays when
+/// dropped, so particles can be changed in place like elements of a `Vec<Particle>`
+pub struct ParticleMut<'a, V: Vector = Vector2<Fp>> {
+    particle: Particle<V>,
+    slot: ParticleSlices<'a, V>,
+}
+
+impl<'a, V: Vector> ParticleMut<'a, V> {
+    fn new(slot: ParticleSlices<'a, V>) -> Self {
+        let first = |slices: &V::Slices<'a>| V::from_fn(|axis| slices.as_ref()[axis][0]);
+        let particle = Particle {
+            pos: first(&slot.pos),
+            vel: first(&slot.vel),
+            accel: first(&slot.accel),
+            mass: slot.mass[0],
+            density: slot.density[0],
+            pressure: slot.pressure[0],
+            vorticity: slot.vorticity[0],
+            temperature: slot.temperature[0],
+            material: slot.material[0],
+        };
+        ParticleMut { particle, slot }
+    }
+}
+
+impl<V: Vector> Deref for ParticleMut<'_, V> {
+    type Target = Particle<V>;
+
+    fn deref(&self) -> &Particle<V> {
+        &self.particle
+    }
+}
+
+impl<V: Vector> DerefMut for ParticleMut<'_, V> {
+    fn deref_mut(&mut self) -> &mut Particle<V> {
+        &mut self.particle
+    }
+}
+
+impl<V: Vector> Drop for ParticleMut<'_, V> {
+    fn drop(&mut self) {
+        let (p, slot) = (&self.particle, &mut self.slot);
+        for axis in 0..V::DIMENSIONS {
+            slot.pos.as_mut()[axis][0] = p.pos[axis];
+            slot.vel.as_mut()[axis][0] = p.vel[axis];
+            slot.accel.as_mut()[axis][0] = p.accel[axis];
+        }
+        slot.mass[0] = p.mass;
+        slot.density[0] = p.density;
+        slot.pressure[0] = p.pressure;
+        slot.vorticity[0] = p.vorticity;
+        slot.temperature[0] = p.temperature;
+        slot.material[0] = p.material;
+    }
+}
+
+/// Iterates over views of the particles in a `Particles`, see `ParticleMut`
+pub struct ParticleIterMut<'a, V: Vector = Vector2<Fp>> {
+    slices: ParticleSlices<'a, V>,
+}
+
+impl<'a, V: Vector> Iterator for ParticleIterMut<'a, V> {
+    type Item = ParticleMut<'a, V>;
+
+    fn next(&mut self) -> Option<ParticleMut<'a, V>> {
+        self.slices.split_first().map(ParticleMut::new)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.slices.mass.len(), Some(self.slices.mass.len()))
+    }
+}
+
+impl<V: Vector> ExactSizeIterator for ParticleIterMut<'_, V> {}
+
+impl<'a, V: Vector> IntoIterator for &'a mut Particles<V> {
+    type Item = ParticleMut<'a, V>;
+    type IntoIter = ParticleIterMut<'a, V>;
+
+    fn into_iter(self) -> ParticleIterMut<'a, V> {
+        self.iter_mut()
+    }
+}
+
+impl<V: Vector> FromIterator<Particle<V>> for Particles<V> {
+    fn from_iter<I: IntoIterator<Item = Particle<V>>>(iter: I) -> Self {
+        let mut particles = Particles::new();
+        iter.into_iter().for_each(|p| particles.push(p));
+        particles
+    }
+}
+
+impl<V: Vector> From<Vec<Particle<V>>> for Particles<V> {
+    fn from(particles: Vec<Particle<V>>) -> Self {
+        particles.into_iter().collect()
+    }
+}
+
+impl<V: Vector> From<Particles<V>> for Vec<Particle<V>> {
+    fn from(particles: Particles<V>) -> Self {
+        particles.iter().collect()
+    }
+}
@@ -1,66 +1,336 @@
+use crate::boundary::apply_kinematic_boundaries;
+use crate::config::{BoundaryCondition, Geometry};
+use crate::cursor_tool::{apply_cursor_tool, CursorState};
+use crate::emitter::{apply_drains, apply_emitters};
+use crate::force_field::{Drag, ForceField};
+use crate::kernels::{poly6, spiky_gradient};
+use crate::math::Vector;
+use crate::neighbour_grid::NeighbourGrid;
+use crate::particle::{Particle, Particles};
+use crate::probe::record_probes;
+use crate::profiler::{time_stage, Profiler};
 use crate::scene_data::SceneData;
-use crate::{CursorState, Fp, CURSOR_FORCE, CURSOR_RADIUS, WORLD_HEIGHT, WORLD_WIDTH};
+use crate::thermal::{apply_heat_transfer, apply_phase_changes};
+use crate::Fp;
 use cgmath::{InnerSpace, Vector2, Zero};
-use rand::Rng;
+use rayon::prelude::*;
+use std::ops::Range;
+use std::time::Instant;
 
 // const GRAVITY: Fp = -9.81;
-const GRAVITY: Fp = -9.81;
-const COEF_OF_REST: Fp = 0.1;
-const DRAG_COEF: Fp = 2.0;
+pub const GRAVITY: Fp = -9.81;
+pub(crate) const COEF_OF_REST: Fp = 0.1;
+pub const DRAG_COEF: Fp = 2.0;
 const PARTICLE_FORCE_SCALE: Fp = 0.0001;
-const PARTICLE_FORCE_DIST_SCALE: Fp = 1.0;
 // const STRONG_PARTICLE_FORCE_SCALE: Fp = 0.0001;
 const WALL_FORCE_SCALE: Fp = 0.005;
+const SOLID_BOND_LENGTH: Fp = 0.025;
+const SOLID_BOND_RANGE: Fp = 1.5 * SOLID_BOND_LENGTH;
+const SOLID_BOND_STIFFNESS: Fp = 150.0;
+const SOLID_BOND_DAMPING: Fp = 5.0;
 
-pub fn physics_update<const C: usize>(
-    scene_data: &mut SceneData<C>,
+pub const INTERACTION_RADIUS: Fp = 0.05;
+pub const REST_DENSITY: Fp = 20000.0; // Typical density in the bulk of a settled tank
+
+const MIN_AXIS_DIST: Fp = INTERACTION_RADIUS; // Rings closer to the axis are treated as if they were this far out
+pub const MAX_REPULSION_DIST: Fp = 0.1;
+
+const CHUNK_SIZE: usize = 4096; // Particles per parallel task in the component-wise passes
+const LANES: usize = 8; // Neighbours a kernel works on at once, enough to fill the vector units in either precision
+
+pub fn physics_update(scene_data: &mut SceneData, delta_time: Fp, cursor_state: &CursorState) {
+    step(scene_data, delta_time, cursor_state, None);
+}
+
+/// `physics_update`, timing each stage with `profiler`
+pub fn physics_update_profiled(
+    scene_data: &mut SceneData,
     delta_time: Fp,
     cursor_state: &CursorState,
+    profiler: &mut Profiler,
 ) {
-    // ! If removed, must be replaced with p.accel = 0 !
-    scene_data
-        .particles
-        .iter_mut()
-        .for_each(|p| p.accel = Vector2::new(0.0, GRAVITY));
-    scene_data
-        .particles
-        .iter_mut()
-        .for_each(|p| p.accel += (-p.vel * DRAG_COEF) / p.mass);
-
-    match cursor_state {
-        CursorState::Pull(pos) => scene_data.particles.iter_mut().for_each(|p| {
-            if (pos - p.pos).magnitude() < CURSOR_RADIUS {
-                p.accel += (pos - p.pos).normalize() * CURSOR_FORCE
+    step(scene_data, delta_time, cursor_state, Some(profiler));
+}
+
+/// One physics step, timing each stage if there's a profiler
+fn step(scene_data: &mut SceneData, delta_time: Fp, cursor_state: &CursorState, mut profiler: Option<&mut Profiler>) {
+    let step_start = profiler.is_some().then(Instant::now);
+    // The last step left the grid up to date, unless particles were added or removed since, e.g.
+    // in a freshly built scene
+    if scene_data.neighbour_grid.particle_count() != scene_data.particles.len() {
+        time_stage(&mut profiler, "neighbour_grid", || scene_data.rebuild_neighbour_grid());
+    }
+
+    time_stage(&mut profiler, "thermal", || {
+        apply_heat_transfer(scene_data, delta_time);
+        apply_phase_changes(scene_data);
+    });
+
+    time_stage(&mut profiler, "densities", || compute_densities(scene_data));
+    time_stage(&mut profiler, "vorticity", || compute_vorticity(scene_data));
+    time_stage(&mut profiler, "force_fields", || apply_force_fields(scene_data, cursor_state));
+
+    time_stage(&mut profiler, "vorticity_confinement", || apply_vorticity_confinement(scene_data));
+
+    time_stage(&mut profiler, "repulsion", || apply_repulsive_particle_force(scene_data));
+
+    time_stage(&mut profiler, "solid_cohesion", || apply_solid_cohesion(scene_data));
+    time_stage(&mut profiler, "probes", || record_probes(scene_data));
+
+    // Rings keep their mass as they move in or out, so an axisymmetric world notes where they
+    // started to rescale the particles' masses per unit length once they've moved
+    let ring_radii = (scene_data.geometry == Geometry::Axisymmetric).then(|| ring_radii(&scene_data.particles));
+    time_stage(&mut profiler, "integration", || integrate_particles(scene_data, delta_time));
+
+    scene_data.time += delta_time;
+
+    time_stage(&mut profiler, "boundaries", || {
+        apply_kinematic_boundaries(scene_data, delta_time);
+        bound_particles(scene_data, delta_time);
+        if let Some(ring_radii) = &ring_radii {
+            conserve_ring_masses(&mut scene_data.particles, ring_radii);
+        }
+        remove_escaped_particles(scene_data);
+    });
+
+    time_stage(&mut profiler, "sources", || {
+        apply_emitters(scene_data, delta_time);
+        apply_drains(scene_data);
+    });
+    time_stage(&mut profiler, "cursor_tool", || apply_cursor_tool(scene_data, cursor_state, delta_time));
+    time_stage(&mut profiler, "neighbour_grid", || scene_data.rebuild_neighbour_grid());
+    if let (Some(profiler), Some(step_start)) = (profiler, step_start) {
+        profiler.record("physics_update", step_start);
+    }
+}
+
+/// Sets every particle's acceleration to the sum of the built-in fields (gravity, drag and wall
+/// repulsion), the fields registered on the scene and the cursor
+pub fn apply_force_fields(scene_data: &mut SceneData, cursor_state: &CursorState) {
+    let time = scene_data.time;
+    let drag = Drag {
+        coefficient: scene_data.config.drag_coefficient,
+    };
+    let wall_repulsion = WallRepulsion {
+        walls_x: scene_data.config.boundary_x == BoundaryCondition::Wall,
+        walls_y: scene_data.config.boundary_y == BoundaryCondition::Wall,
+        axis: scene_data.geometry == Geometry::Axisymmetric,
+        tank_offset: scene_data.tank_motion.state(time).offset,
+        tank_size: scene_data.size,
+    };
+    let cursor_field = cursor_state.force_field();
+
+    let mut fields: Vec<&dyn ForceField> = vec![&scene_data.config.gravity, &drag, &wall_repulsion];
+    fields.extend(scene_data.force_fields.iter().map(|f| f.as_ref()));
+    if let Some(cursor_field) = &cursor_field {
+        fields.push(cursor_field);
+    }
+
+    let particles = &scene_data.particles;
+    let accels: Vec<Vector2<Fp>> = (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let particle = particles.get(i);
+            let mut accel = Vector2::zero();
+            for field in &fields {
+                accel += field.acceleration(&particle, time);
+            }
+            accel
+        })
+        .collect();
+    set_accels(&mut scene_data.particles, &accels);
+}
+
+pub fn compute_densities(scene_data: &mut SceneData) {
+    if scene_data.geometry == Geometry::Axisymmetric {
+        compute_ring_densities(scene_data);
+        return;
+    }
+
+    scene_data.particles.density = particle_densities(&scene_data.neighbour_grid, &scene_data.particles);
+}
+
+/// Each particle's density, summed over the particles within `INTERACTION_RADIUS`, in 2D or 3D
+pub fn particle_densities<V: Vector>(grid: &NeighbourGrid<V>, particles: &Particles<V>) -> Vec<Fp> {
+    let sorted = SortedParticles::new(grid, particles);
+    (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let [density] =
+                sum_over_candidates(grid, &sorted, particles.pos(i), INTERACTION_RADIUS, |displacement, mass| {
+                    [mass * poly6(displacement)]
+                });
+            density
+        })
+        .collect()
+}
+
+/// Positions and masses in the neighbour grid's order, so each run of cells the grid returns is
+/// a contiguous slice of each array
+struct SortedParticles<V: Vector> {
+    pos: V::Columns,
+    mass: Vec<Fp>,
+}
+
+impl<V: Vector> SortedParticles<V> {
+    fn new(grid: &NeighbourGrid<V>, particles: &Particles<V>) -> Self {
+        let sort = |values: &[Fp]| grid.order().iter().map(|&j| values[j]).collect();
+        let mut pos = V::Columns::default();
+        for (sorted, values) in pos.as_mut().iter_mut().zip(particles.pos.as_ref()) {
+            *sorted = sort(values);
+        }
+        SortedParticles {
+            pos,
+            mass: sort(&particles.mass),
+        }
+    }
+
+    /// The positions along `axis` in `run`, or nothing on an axis `V` doesn't have
+    fn axis(&self, axis: usize, run: Range<usize>) -> &[Fp] {
+        self.pos.as_ref().get(axis).map_or(&[], |values| &values[run])
+    }
+}
+
+/// Sums `kernel(displacement, mass)` over every candidate within `range` of `pos`, including a
+/// particle at `pos` itself. The candidates are taken `LANES` at a time from the sorted arrays,
+/// with a partial sum per lane, so the kernel vectorises.
+fn sum_over_candidates<V: Vector, const K: usize>(
+    grid: &NeighbourGrid<V>,
+    sorted: &SortedParticles<V>,
+    pos: V,
+    range: Fp,
+    kernel: impl Fn(V, Fp) -> [Fp; K] + Copy,
+) -> [Fp; K] {
+    let mut totals = [[0.0; LANES]; K];
+    let wrapping = grid.wrapping();
+    grid.for_each_candidate_run(pos, range, |run| {
+        // Locals rather than the captures, so they stay in registers
+        let (mut lanes, wrapping, kernel) = (totals, wrapping, kernel);
+        let axes: [&[Fp]; 3] = std::array::from_fn(|axis| sorted.axis(axis, run.clone()));
+        let chunks = axes.map(|values| values.as_chunks::<LANES>().0);
+        let (mass, mass_rest) = sorted.mass[run].as_chunks::<LANES>();
+        for (chunk, mass) in mass.iter().enumerate() {
+            let values: [[Fp; K]; LANES] = std::array::from_fn(|lane| {
+                let displacement = V::from_fn(|axis| wrapping.apply(axis, chunks[axis][chunk][lane] - pos[axis]));
+                kernel(displacement, mass[lane])
+            });
+            for k in 0..K {
+                lanes[k] = std::array::from_fn(|lane| lanes[k][lane] + values[lane][k]);
             }
-        }),
-        CursorState::Push(pos) => scene_data.particles.iter_mut().for_each(|p| {
-            if (pos - p.pos).magnitude() < CURSOR_RADIUS {
-                p.accel += -(pos - p.pos).normalize() * CURSOR_FORCE
+        }
+        let done = mass.len() * LANES;
+        for (lane, mass) in mass_rest.iter().enumerate() {
+            let displacement = V::from_fn(|axis| wrapping.apply(axis, axes[axis][done + lane] - pos[axis]));
+            let values = kernel(displacement, *mass);
+            for k in 0..K {
+                lanes[k][lane] += values[k];
             }
-        }),
-        CursorState::None => {}
+        }
+        totals = lanes;
+    });
+    totals.map(|lane| lane.iter().sum())
+}
+
+/// Densities in an axisymmetric world. A ring's mass grows with its radius, so neighbours are
+/// weighted by theirs, and the sum is divided by the kernel weighted mean radius around the
+/// particle rather than its own radius, which matches it away from the axis and stays finite on
+/// it. Mirror images of the particles near the axis fill in the half of the kernel beyond it.
+/// Where every neighbour is on the axis the mean radius is zero, so the density falls back to the
+/// plain kernel sum the ring sum tends to there, keeping it positive to divide by.
+fn compute_ring_densities(scene_data: &mut SceneData) {
+    let particles = &scene_data.particles;
+    let grid = &scene_data.neighbour_grid;
+    let densities = (0..particles.len())
+        .into_par_iter()
+        .map(|i| ring_density(grid, particles, particles.pos(i)))
+        .collect();
+    scene_data.particles.density = densities;
+}
+
+/// Density of the rings around `pos` in an axisymmetric world, see `compute_ring_densities`
+pub(crate) fn ring_density(grid: &NeighbourGrid, particles: &Particles, pos: Vector2<Fp>) -> Fp {
+    let (mut ring_mass, mut radius, mut weight, mut mass) = (0.0, 0.0, 0.0, 0.0);
+    grid.for_each_candidate(pos, |j| {
+        let pos_j = particles.pos(j);
+        for other in axis_images(pos, pos_j, INTERACTION_RADIUS) {
+            let w = poly6(grid.displacement(pos, other));
+            ring_mass += particles.mass[j] * pos_j.x * w;
+            radius += pos_j.x * w;
+            weight += w;
+            mass += particles.mass[j] * w;
+        }
+    });
+    if radius > 0.0 {
+        ring_mass * weight / radius
+    } else {
+        mass
     }
+}
+
+/// `pos_j`, followed by its mirror image across the axis if that's within `range` of `pos`
+pub(crate) fn axis_images(pos: Vector2<Fp>, pos_j: Vector2<Fp>, range: Fp) -> impl Iterator<Item = Vector2<Fp>> {
+    let mirrored = (pos.x + pos_j.x < range).then(|| Vector2::new(-pos_j.x, pos_j.y));
+    std::iter::once(pos_j).chain(mirrored)
+}
 
-    apply_repulsive_particle_force(scene_data);
+/// Computes the (scalar, out of plane) curl of the velocity field at each particle.
+/// Relies on densities being up to date.
+pub fn compute_vorticity(scene_data: &mut SceneData) {
+    let particles = &scene_data.particles;
+    let grid = &scene_data.neighbour_grid;
+    let vorticities = (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let (pos, vel) = (particles.pos(i), particles.vel(i));
+            let mut vorticity = 0.0;
+            grid.for_each_candidate(pos, |j| {
+                let gradient = spiky_gradient(grid.displacement(particles.pos(j), pos));
+                let relative_vel = particles.vel(j) - vel;
+                vorticity += (particles.mass[j] / particles.density[j])
+                    * (gradient.x * relative_vel.y - gradient.y * relative_vel.x);
+            });
+            vorticity
+        })
+        .collect();
+    scene_data.particles.vorticity = vorticities;
+}
 
-    apply_repulsive_wall_force(scene_data);
+/// Re-injects rotation lost to drag and smoothing by pushing particles around vorticity peaks
+/// (Fedkiw et al. 2001). Relies on vorticity being up to date.
+pub fn apply_vorticity_confinement(scene_data: &mut SceneData) {
+    let epsilon = scene_data.config.vorticity_epsilon;
+    if epsilon == 0.0 {
+        return;
+    }
 
-    scene_data
-        .particles
-        .iter_mut()
-        .for_each(|p| p.apply_accel(delta_time));
-    scene_data
-        .particles
-        .iter_mut()
-        .for_each(|p| p.apply_vel(delta_time));
+    let particles = &scene_data.particles;
+    let grid = &scene_data.neighbour_grid;
+    let accels: Vec<Vector2<Fp>> = (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let (pos, vorticity) = (particles.pos(i), particles.vorticity[i]);
+            // Gradient of the vorticity magnitude points towards the centre of the swirl
+            let mut magnitude_gradient = Vector2::zero();
+            grid.for_each_candidate(pos, |j| {
+                magnitude_gradient += spiky_gradient(grid.displacement(particles.pos(j), pos))
+                    * ((particles.mass[j] / particles.density[j])
+                        * (particles.vorticity[j].abs() - vorticity.abs()));
+            });
 
-    bound_particles(scene_data, delta_time);
+            if magnitude_gradient.magnitude2() < Fp::EPSILON {
+                return Vector2::zero();
+            }
+            let n = magnitude_gradient.normalize();
+            Vector2::new(n.y, -n.x) * (vorticity * epsilon)
+        })
+        .collect();
+    add_accels(&mut scene_data.particles, &accels);
 }
 
-pub fn get_force(pos1: Vector2<Fp>, pos2: Vector2<Fp>, force_scale: Fp) -> Vector2<Fp> {
+pub fn get_force<V: Vector>(pos1: V, pos2: V, force_scale: Fp) -> V {
     let mut displacement = pos2 - pos1; // 1 to 2
-    if displacement == Vector2::<Fp>::zero() {
-        displacement = Vector2::new(0.01, 0.01);
+    if displacement.is_zero() {
+        displacement = V::from_fn(|_| 0.01);
     }
 
     let mut distance = displacement.magnitude();
@@ -72,68 +342,326 @@ pub fn get_force(pos1: Vector2<Fp>, pos2: Vector2<Fp>, force_scale: Fp) -> Vecto
     -direction * force
 }
 
-pub fn apply_repulsive_particle_force<const C: usize>(scene_data: &mut SceneData<C>) {
-    for i in 0..C {
-        for j in 0..C {
-            if i == j {
-                continue;
-            }
+/// Pushes nearby particles apart, and sets each particle's pressure from the forces it feels
+pub fn apply_repulsive_particle_force(scene_data: &mut SceneData) {
+    if scene_data.geometry == Geometry::Axisymmetric {
+        apply_ring_repulsion(scene_data);
+        return;
+    }
 
-            const MAX_DIST: Fp = 0.1;
-            //
-            // let x_dist = (scene_data.particles[i].pos.x - scene_data.particles[j].pos.x);
-            // let y_dist = (scene_data.particles[i].pos.y - scene_data.particles[j].pos.y);
-            // if x_dist > MAX_DIST || x_dist < -MAX_DIST || y_dist > MAX_DIST || y_dist < -MAX_DIST { continue; }
+    // The force on each side of a pair is doubled, as the serial pass visited every pair in both
+    // orders and pushed both particles each time, which cancelled out for coincident particles
+    let (accels, pressures) =
+        particle_repulsion(&scene_data.neighbour_grid, &scene_data.particles, 2.0 * PARTICLE_FORCE_SCALE);
+    set_accels(&mut scene_data.particles, &accels);
+    scene_data.particles.pressure = pressures;
+}
 
-            if (scene_data.particles[i].pos - scene_data.particles[j].pos).magnitude() > MAX_DIST { continue; }
+/// Each particle's acceleration plus that from the repulsion of the particles within
+/// `MAX_REPULSION_DIST`, which follows the inverse square law of `get_force` with a scale of
+/// `force_scale`, and the pressure the repulsion sets up, in 2D or 3D. Each particle gathers the
+/// forces from its neighbours, so results don't depend on the number of threads.
+pub fn particle_repulsion<V: Vector>(
+    grid: &NeighbourGrid<V>,
+    particles: &Particles<V>,
+    force_scale: Fp,
+) -> (Vec<V>, Vec<Fp>) {
+    let sorted = SortedParticles::new(grid, particles);
+    (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            // The force along each axis, with the virial of each pair's force
+            let [force_x, force_y, force_z, virial] =
+                sum_over_candidates(grid, &sorted, particles.pos(i), MAX_REPULSION_DIST, move |displacement: V, _| {
+                    let distance = displacement.magnitude();
+                    let clamped = distance.max(0.01);
+                    let strength = if distance > 0.0 && distance <= MAX_REPULSION_DIST {
+                        force_scale / (clamped * clamped * distance)
+                    } else {
+                        0.0
+                    };
+                    let force = |axis| if axis < V::DIMENSIONS { -displacement[axis] * strength } else { 0.0 };
+                    [force(0), force(1), force(2), distance * distance * strength]
+                });
+            let (mass, density) = (particles.mass[i], particles.density[i]);
+            let force = V::from_fn(|axis| [force_x, force_y, force_z][axis]);
+            (particles.accel(i) + force / mass, virial_pressure::<V>(virial, mass, density))
+        })
+        .unzip()
+}
 
-            let force = get_force(scene_data.particles[i].pos, scene_data.particles[j].pos, PARTICLE_FORCE_SCALE);
-            scene_data.particles[i].accel += force / scene_data.particles[i].mass;
-            scene_data.particles[j].accel += -force / scene_data.particles[j].mass;
-        }
+/// Pressure at a particle from the virial of the pair forces on it, the sum of each force dotted
+/// with the separation it acts across. Half of each pair's virial belongs to each particle, and
+/// the pressure over a volume, here `mass / density`, is the virial divided by the number of
+/// dimensions times the volume.
+fn virial_pressure<V: Vector>(virial: Fp, mass: Fp, density: Fp) -> Fp {
+    virial / 2.0 / (V::DIMENSIONS as Fp * mass / density)
+}
+
+/// Repulsion between the rings particles stand for in an axisymmetric world. A ring is a fixed
+/// amount of fluid spread around its circumference, and each pair of rings pushes across the
+/// face between them in proportion to its mean radius and to how much fluid each ring has per
+/// unit length. Both forces come from the energy of the pairs' repulsion: the push across the
+/// face from its change with the separation, and an outward hoop force from its change with the
+/// radius, as stretching a ring spreads its fluid more thinly. In still fluid the hoop force
+/// balances the extra push on the outer face of each ring. Mirror images of the particles near
+/// the axis push back like the fluid on its far side would.
+fn apply_ring_repulsion(scene_data: &mut SceneData) {
+    let particles = &scene_data.particles;
+    let grid = &scene_data.neighbour_grid;
+    let (accels, pressures): (Vec<Vector2<Fp>>, Vec<Fp>) = (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let pos = particles.pos(i);
+            let radius = ring_radius(pos.x);
+            // Rings held off the axis don't stretch as they move
+            let stretches = pos.x > MIN_AXIS_DIST;
+            let (mass, density) = (particles.mass[i], particles.density[i]);
+            let (mut accel, mut virial) = (particles.accel(i), 0.0);
+            grid.for_each_candidate_within(pos, MAX_REPULSION_DIST, |j| {
+                for (image, other) in axis_images(pos, particles.pos(j), MAX_REPULSION_DIST).enumerate() {
+                    let displacement = grid.displacement(pos, other);
+                    let distance = displacement.magnitude();
+                    if (i == j && image == 0) || distance > MAX_REPULSION_DIST {
+                        continue;
+                    }
+                    // Doubled to match the planar pass, which applies each pair once in both orders
+                    let other_mass = particles.mass[j];
+                    let force = get_force(pos, pos + displacement, 2.0 * PARTICLE_FORCE_SCALE) * other_mass;
+                    let other_radius = ring_radius(other.x.abs());
+                    accel += force * ((radius + other_radius) / (2.0 * radius));
+                    if stretches {
+                        accel.x += repulsion_energy(distance) * other_mass * other_radius / (2.0 * radius * radius);
+                    }
+                    virial -= displacement.dot(force) * mass;
+                }
+            });
+            (accel, virial_pressure::<Vector2<Fp>>(virial, mass, density))
+        })
+        .unzip();
+    set_accels(&mut scene_data.particles, &accels);
+    scene_data.particles.pressure = pressures;
+}
+
+/// Energy of the doubled repulsion between a pair of unit masses `distance` apart, zero at
+/// `MAX_REPULSION_DIST`, so that `get_force` is its negative gradient
+fn repulsion_energy(distance: Fp) -> Fp {
+    let clamped = distance.max(0.01);
+    let scale = 2.0 * PARTICLE_FORCE_SCALE;
+    scale * (1.0 / clamped - 1.0 / MAX_REPULSION_DIST + (clamped - distance) / (clamped * clamped))
+}
+
+/// Radius of the ring a particle at `x` stands for, held off the axis
+pub(crate) fn ring_radius(x: Fp) -> Fp {
+    x.max(MIN_AXIS_DIST)
+}
+
+/// Radii of every particle's ring, to keep the rings' masses with `conserve_ring_masses` after
+/// the particles move
+fn ring_radii(particles: &Particles) -> Vec<Fp> {
+    particles.pos[0].iter().map(|&x| ring_radius(x)).collect()
+}
+
+/// Rescales each particle's mass per unit length for the radius its ring has moved to from
+/// `old_radii`, so the ring's total mass stays the same
+fn conserve_ring_masses(particles: &mut Particles, old_radii: &[Fp]) {
+    for ((mass, &x), &old_radius) in particles.mass.iter_mut().zip(&particles.pos[0]).zip(old_radii) {
+        *mass *= old_radius / ring_radius(x);
     }
 }
 
-pub fn apply_repulsive_wall_force<const C: usize>(scene_data: &mut SceneData<C>) {
-    for particle in &mut scene_data.particles {
+/// Pushes particles away from the world walls on axes with wall boundaries
+pub struct WallRepulsion {
+    pub walls_x: bool,
+    pub walls_y: bool,
+    /// The left edge is the axis of an axisymmetric world rather than a wall, and particle masses
+    /// are per unit length of their rings
+    pub axis: bool,
+    pub tank_offset: Vector2<Fp>,
+    pub tank_size: Vector2<Fp>,
+}
+
+impl ForceField for WallRepulsion {
+    fn acceleration(&self, particle: &Particle, _time: Fp) -> Vector2<Fp> {
         let pos = particle.pos;
+        let (left, bottom) = (self.tank_offset.x, self.tank_offset.y);
+        let (right, top) = (
+            self.tank_size.x + self.tank_offset.x,
+            self.tank_size.y + self.tank_offset.y,
+        );
 
         let mut total_force = Vector2::zero();
-        total_force += get_force(pos, Vector2::new(-0.01, pos.y), WALL_FORCE_SCALE);
-        total_force += get_force(pos, Vector2::new(WORLD_WIDTH + 0.01, pos.y), WALL_FORCE_SCALE);
-        total_force += get_force(pos, Vector2::new(pos.x, -0.01), WALL_FORCE_SCALE);
-        total_force += get_force(pos, Vector2::new(pos.x, WORLD_HEIGHT + 0.01), WALL_FORCE_SCALE);
-        particle.accel += total_force / particle.mass;
-    }
-}
-
-pub fn bound_particles<const C: usize>(scene_data: &mut SceneData<C>, delta_time: Fp) {
-    for particle in &mut scene_data.particles {
-        // for (pos, vel, accel) in [(&mut particle.pos.x, &mut particle.vel.x, &mut particle.accel.x), (&mut particle.pos.y, &mut particle.vel.y, &mut particle.accel.y)] {
-        //     if *pos >= 0.0 { continue; }
-        //     // let dist_moved = *vel * delta_time;
-        //     // let dist_illegal = -*pos;
-        //     // let proportion =  (dist_illegal / dist_moved).abs();
-        //     *vel = -*vel;
-        //     // *vel += *accel * delta_time * proportion;
-        //     *pos = -*pos;
-        // }
-
-        if particle.pos.x < 0.0 {
-            particle.pos.x = -particle.pos.x;
-            particle.vel.x = -particle.vel.x * COEF_OF_REST;
+        if self.walls_x {
+            if !self.axis {
+                total_force += get_force(pos, Vector2::new(left - 0.01, pos.y), WALL_FORCE_SCALE);
+            }
+            total_force += get_force(pos, Vector2::new(right + 0.01, pos.y), WALL_FORCE_SCALE);
+        }
+        if self.walls_y {
+            total_force += get_force(pos, Vector2::new(pos.x, bottom - 0.01), WALL_FORCE_SCALE);
+            total_force += get_force(pos, Vector2::new(pos.x, top + 0.01), WALL_FORCE_SCALE);
         }
-        if particle.pos.y < 0.0 {
-            particle.pos.y = -particle.pos.y;
-            particle.vel.y = -particle.vel.y * COEF_OF_REST;
+        if self.axis {
+            // The walls push on all of a ring's fluid, however thinly it's spread
+            total_force
+        } else {
+            total_force / particle.mass
         }
-        if particle.pos.x > WORLD_WIDTH {
-            particle.pos.x = WORLD_WIDTH - (particle.pos.x - WORLD_WIDTH);
-            particle.vel.x = -particle.vel.x * COEF_OF_REST;
+    }
+}
+
+/// Holds neighbouring solid particles at a fixed spacing so solids behave like an elastic lattice
+pub fn apply_solid_cohesion(scene_data: &mut SceneData) {
+    let particles = &scene_data.particles;
+    let grid = &scene_data.neighbour_grid;
+    let accels: Vec<Vector2<Fp>> = (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let mut total_force = Vector2::zero();
+            if particles.material[i].is_solid() {
+                let (pos, vel) = (particles.pos(i), particles.vel(i));
+                grid.for_each_candidate(pos, |j| {
+                    if i == j || !particles.material[j].is_solid() {
+                        return;
+                    }
+                    let displacement = grid.displacement(pos, particles.pos(j));
+                    let distance = displacement.magnitude();
+                    if distance > SOLID_BOND_RANGE || distance == 0.0 {
+                        return;
+                    }
+                    let direction = displacement / distance;
+                    let stretch = distance - SOLID_BOND_LENGTH;
+                    let closing_speed = (particles.vel(j) - vel).dot(direction);
+                    total_force += direction
+                        * (stretch * SOLID_BOND_STIFFNESS + closing_speed * SOLID_BOND_DAMPING);
+                });
+            }
+            total_force / particles.mass[i]
+        })
+        .collect();
+    add_accels(&mut scene_data.particles, &accels);
+}
+
+pub fn bound_particles(scene_data: &mut SceneData, _delta_time: Fp) {
+    let boundary_x = scene_data.config.boundary_x;
+    let boundary_y = scene_data.config.boundary_y;
+
+    // Walls move with the tank and bounce particles relative to the tank's velocity
+    let tank = scene_data.tank_motion.state(scene_data.time);
+    let size = scene_data.size;
+    let (left, bottom) = (tank.offset.x, tank.offset.y);
+    let (right, top) = (size.x + tank.offset.x, size.y + tank.offset.y);
+
+    let [x, y] = &mut scene_data.particles.pos;
+    let [vx, vy] = &mut scene_data.particles.vel;
+    if scene_data.geometry == Geometry::Axisymmetric {
+        reflect_axis(x, vx);
+    }
+    bound_axis(x, vx, boundary_x, (left, right), tank.velocity.x, size.x);
+    bound_axis(y, vy, boundary_y, (bottom, top), tank.velocity.y, size.y);
+}
+
+/// Removes the particles that left the tank across open boundaries
+pub fn remove_escaped_particles(scene_data: &mut SceneData) {
+    let boundary_x = scene_data.config.boundary_x;
+    let boundary_y = scene_data.config.boundary_y;
+    if boundary_x == BoundaryCondition::Open || boundary_y == BoundaryCondition::Open {
+        let (offset, size) = (scene_data.tank_motion.state(scene_data.time).offset, scene_data.size);
+        let (x_range, y_range) = (offset.x..=size.x + offset.x, offset.y..=size.y + offset.y);
+        scene_data.particles.retain(|p| {
+            (boundary_x != BoundaryCondition::Open || x_range.contains(&p.pos.x))
+                && (boundary_y != BoundaryCondition::Open || y_range.contains(&p.pos.y))
+        });
+    }
+}
+
+/// Mirrors particles that crossed the axis of an axisymmetric world back across it. A ring can't
+/// pass through its own axis, the fluid on the far side is the same ring coming the other way.
+fn reflect_axis(pos: &mut [Fp], vel: &mut [Fp]) {
+    pos.par_chunks_mut(CHUNK_SIZE)
+        .zip(vel.par_chunks_mut(CHUNK_SIZE))
+        .for_each(|(pos, vel)| {
+            for (pos, vel) in pos.iter_mut().zip(vel) {
+                if *pos < 0.0 {
+                    *pos = -*pos;
+                    *vel = -*vel;
+                }
+            }
+        });
+}
+
+/// Bounces, wraps or leaves alone the particles on one axis, given their positions and velocities
+/// along it. `wall_vel` is the velocity along the axis of the walls at `low` and `high`, and
+/// periodic axes wrap into `low..low + world_size`.
+pub(crate) fn bound_axis(
+    pos: &mut [Fp],
+    vel: &mut [Fp],
+    boundary: BoundaryCondition,
+    (low, high): (Fp, Fp),
+    wall_vel: Fp,
+    world_size: Fp,
+) {
+    pos.par_chunks_mut(CHUNK_SIZE)
+        .zip(vel.par_chunks_mut(CHUNK_SIZE))
+        .for_each(|(pos, vel)| match boundary {
+            BoundaryCondition::Wall => {
+                for (pos, vel) in pos.iter_mut().zip(vel) {
+                    if *pos < low {
+                        *pos = low + (low - *pos);
+                        *vel = wall_vel - (*vel - wall_vel) * COEF_OF_REST;
+                    }
+                    if *pos > high {
+                        *pos = high - (*pos - high);
+                        *vel = wall_vel - (*vel - wall_vel) * COEF_OF_REST;
+                    }
+                }
+            }
+            BoundaryCondition::Periodic => {
+                pos.iter_mut().for_each(|pos| *pos = low + (*pos - low).rem_euclid(world_size))
+            }
+            BoundaryCondition::Open => {}
+        });
+}
+
+/// Steps every particle's velocity and then position forward by `delta_time`
+pub fn integrate_particles(scene_data: &mut SceneData, delta_time: Fp) {
+    integrate_motion(&mut scene_data.particles, delta_time);
+}
+
+/// Steps the velocities and then the positions of `particles` forward by `delta_time`, in 2D or 3D
+pub(crate) fn integrate_motion<V: Vector>(particles: &mut Particles<V>, delta_time: Fp) {
+    for (vel, accel) in particles.vel.as_mut().iter_mut().zip(particles.accel.as_ref()) {
+        integrate(vel, accel, delta_time);
+    }
+    for (pos, vel) in particles.pos.as_mut().iter_mut().zip(particles.vel.as_ref()) {
+        integrate(pos, vel, delta_time);
+    }
+}
+
+/// `values += rates * delta_time`, over chunks big enough for the inner loop to vectorise
+fn integrate(values: &mut [Fp], rates: &[Fp], delta_time: Fp) {
+    values
+        .par_chunks_mut(CHUNK_SIZE)
+        .zip(rates.par_chunks(CHUNK_SIZE))
+        .for_each(|(values, rates)| {
+            for (value, rate) in values.iter_mut().zip(rates) {
+                *value += rate * delta_time;
+            }
+        });
+}
+
+pub(crate) fn set_accels<V: Vector>(particles: &mut Particles<V>, accels: &[V]) {
+    for (axis, column) in particles.accel.as_mut().iter_mut().enumerate() {
+        for (a, accel) in column.iter_mut().zip(accels) {
+            *a = accel[axis];
         }
-        if particle.pos.y > WORLD_HEIGHT {
-            particle.pos.y = WORLD_HEIGHT - (particle.pos.y - WORLD_HEIGHT);
-            particle.vel.y = -particle.vel.y * COEF_OF_REST;
+    }
+}
+
+fn add_accels(particles: &mut Particles, accels: &[Vector2<Fp>]) {
+    for (axis, column) in particles.accel.iter_mut().enumerate() {
+        for (a, accel) in column.iter_mut().zip(accels) {
+            *a += accel[axis];
         }
     }
 }
@@ -0,0 +1,273 @@
+use crate::physics::{INTERACTION_RADIUS, REST_DENSITY};
+use crate::sampling::{sample_point, PointSample};
+use crate::scene_data::SceneData;
+use crate::Fp;
+use cgmath::{InnerSpace, Vector2, Zero};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const SAMPLE_SPACING: Fp = INTERACTION_RADIUS / 2.0; // Between interpolation points along lines and in boxes
+const WET_DENSITY: Fp = REST_DENSITY / 2.0; // Density above which a line gauge counts as underwater
+
+/// Where a probe measures
+pub enum ProbeShape {
+    Point(Vector2<Fp>),
+    /// A gauge, e.g. a vertical line from the floor to measure wave height
+    Line {
+        start: Vector2<Fp>,
+        end: Vector2<Fp>,
+    },
+    Box {
+        min: Vector2<Fp>,
+        max: Vector2<Fp>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Quantity {
+    /// Interpolated pressure, averaged over lines and boxes
+    Pressure,
+    /// Interpolated velocity, averaged over lines and boxes
+    Velocity,
+    /// For points, the height of the highest particle above the point within one interaction
+    /// radius either side. For lines, the distance from the start to the last wet point.
+    /// For boxes, the height of the highest particle inside above the bottom.
+    WaterHeight,
+    /// Particles within an interaction radius of a point, half of one of a line, or inside a box
+    ParticleCount,
+}
+
+impl Quantity {
+    pub const ALL: [Quantity; 4] = [
+        Quantity::Pressure,
+        Quantity::Velocity,
+        Quantity::WaterHeight,
+        Quantity::ParticleCount,
+    ];
+
+    fn column_names(&self, probe: &str) -> Vec<String> {
+        match self {
+            Quantity::Pressure => vec![format!("{}_pressure", probe)],
+            Quantity::Velocity => vec![format!("{}_vx", probe), format!("{}_vy", probe)],
+            Quantity::WaterHeight => vec![format!("{}_height", probe)],
+            Quantity::ParticleCount => vec![format!("{}_count", probe)],
+        }
+    }
+}
+
+/// A named sensor that reads quantities every step
+pub struct Probe {
+    pub name: String,
+    pub shape: ProbeShape,
+    pub quantities: Vec<Quantity>,
+    /// Values from the latest step, in the order of `column_names`. See `ProbeLog` to keep them.
+    pub reading: Vec<Fp>,
+}
+
+impl Probe {
+    /// Records every quantity
+    pub fn new(name: &str, shape: ProbeShape) -> Self {
+        Probe {
+            name: name.to_string(),
+            shape,
+            quantities: Quantity::ALL.to_vec(),
+            reading: Vec::new(),
+        }
+    }
+
+    pub fn with_quantities(mut self, quantities: &[Quantity]) -> Self {
+        self.quantities = quantities.to_vec();
+        self
+    }
+
+    /// Parses `name:point:x,y`, `name:line:x,y:x,y` or `name:box:x,y:x,y`
+    pub fn from_spec(text: &str) -> Option<Probe> {
+        let parse_point = |text: &str| {
+            let (x, y) = text.split_once(',')?;
+            Some(Vector2::new(x.parse().ok()?, y.parse().ok()?))
+        };
+        let parts: Vec<&str> = text.split(':').collect();
+        let shape = match parts[..] {
+            [_, "point", point] => ProbeShape::Point(parse_point(point)?),
+            [_, "line", start, end] => ProbeShape::Line {
+                start: parse_point(start)?,
+                end: parse_point(end)?,
+            },
+            [_, "box", a, b] => {
+                let (a, b) = (parse_point(a)?, parse_point(b)?);
+                ProbeShape::Box {
+                    min: Vector2::new(a.x.min(b.x), a.y.min(b.y)),
+                    max: Vector2::new(a.x.max(b.x), a.y.max(b.y)),
+                }
+            }
+            _ => return None,
+        };
+        Some(Probe::new(parts[0], shape))
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.quantities
+            .iter()
+            .flat_map(|q| q.column_names(&self.name))
+            .collect()
+    }
+
+    /// Points the field quantities are interpolated at and averaged over
+    fn sample_points(&self) -> Vec<Vector2<Fp>> {
+        match self.shape {
+            ProbeShape::Point(pos) => vec![pos],
+            ProbeShape::Line { start, end } => {
+                let count = ((end - start).magnitude() / SAMPLE_SPACING).ceil() as usize + 1;
+                (0..count)
+                    .map(|i| start + (end - start) * (i as Fp / (count - 1).max(1) as Fp))
+                    .collect()
+            }
+            ProbeShape::Box { min, max } => {
+                let size = max - min;
+                let columns = ((size.x / SAMPLE_SPACING).ceil() as usize).max(1);
+                let rows = ((size.y / SAMPLE_SPACING).ceil() as usize).max(1);
+                (0..rows)
+                    .flat_map(|row| {
+                        (0..columns).map(move |column| {
+                            min + Vector2::new(
+                                (column as Fp + 0.5) / columns as Fp * size.x,
+                                (row as Fp + 0.5) / rows as Fp * size.y,
+                            )
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn contains(&self, pos: Vector2<Fp>) -> bool {
+        match self.shape {
+            ProbeShape::Point(centre) => (pos - centre).magnitude() < INTERACTION_RADIUS,
+            ProbeShape::Line { start, end } => {
+                let segment = end - start;
+                let t = if segment.magnitude2() > 0.0 {
+                    ((pos - start).dot(segment) / segment.magnitude2()).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (pos - (start + segment * t)).magnitude() < INTERACTION_RADIUS / 2.0
+            }
+            ProbeShape::Box { min, max } => {
+                pos.x >= min.x && pos.x <= max.x && pos.y >= min.y && pos.y <= max.y
+            }
+        }
+    }
+
+    fn water_height(&self, scene_data: &SceneData, samples: &[PointSample]) -> Fp {
+        let highest_above = |base: Vector2<Fp>, in_range: &dyn Fn(Vector2<Fp>) -> bool| {
+            scene_data
+                .particles
+                .iter()
+                .filter(|p| in_range(p.pos))
+                .map(|p| p.pos.y - base.y)
+                .fold(0.0, Fp::max)
+        };
+        match self.shape {
+            ProbeShape::Point(pos) => {
+                highest_above(pos, &|p| (p.x - pos.x).abs() < INTERACTION_RADIUS && p.y >= pos.y)
+            }
+            ProbeShape::Line { start, end } => {
+                let length = (end - start).magnitude();
+                let last_wet = samples.iter().rposition(|s| s.density > WET_DENSITY);
+                last_wet.map_or(0.0, |i| length * i as Fp / (samples.len() - 1).max(1) as Fp)
+            }
+            ProbeShape::Box { min, .. } => highest_above(min, &|p| self.contains(p)),
+        }
+    }
+
+    fn record(&mut self, scene_data: &SceneData) {
+        let samples: Vec<PointSample> = self
+            .sample_points()
+            .into_iter()
+            .map(|point| sample_point(scene_data, point))
+            .collect();
+        let count = samples.len().max(1) as Fp;
+
+        let mut row = Vec::new();
+        for quantity in &self.quantities {
+            match quantity {
+                Quantity::Pressure => {
+                    row.push(samples.iter().map(|s| s.pressure).sum::<Fp>() / count)
+                }
+                Quantity::Velocity => {
+                    let velocity = samples
+                        .iter()
+                        .fold(Vector2::zero(), |total, s| total + s.velocity)
+                        / count;
+                    row.push(velocity.x);
+                    row.push(velocity.y);
+                }
+                Quantity::WaterHeight => row.push(self.water_height(scene_data, &samples)),
+                Quantity::ParticleCount => row.push(
+                    scene_data
+                        .particles
+                        .iter()
+                        .filter(|p| self.contains(p.pos))
+                        .count() as Fp,
+                ),
+            }
+        }
+        self.reading = row;
+    }
+}
+
+/// Takes a reading from every probe. Needs the neighbour grid, densities and pressures to be
+/// up to date.
+pub fn record_probes(scene_data: &mut SceneData) {
+    if scene_data.probes.is_empty() {
+        return;
+    }
+    let mut probes = std::mem::take(&mut scene_data.probes);
+    probes.iter_mut().for_each(|probe| probe.record(scene_data));
+    scene_data.probe_time = scene_data.time;
+    scene_data.probes = probes;
+}
+
+/// Writes one row of probe readings per call to a CSV file, with a `time` column followed by
+/// each probe's columns
+pub struct ProbeLog {
+    writer: BufWriter<File>,
+    columns: usize,
+}
+
+impl ProbeLog {
+    /// Takes the columns from the scene's probes, which have to stay the same while logging
+    pub fn create(path: impl AsRef<Path>, scene_data: &SceneData) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut header = vec![String::from("time")];
+        for probe in &scene_data.probes {
+            header.extend(probe.column_names());
+        }
+        writeln!(writer, "{}", header.join(","))?;
+        Ok(ProbeLog {
+            writer,
+            columns: header.len() - 1,
+        })
+    }
+
+    /// Writes the readings from the last step
+    pub fn record(&mut self, scene_data: &SceneData) -> io::Result<()> {
+        let columns: usize = scene_data.probes.iter().map(|p| p.reading.len()).sum();
+        if columns != self.columns {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the scene's probes changed since the log was created",
+            ));
+        }
+        write!(self.writer, "{}", scene_data.probe_time)?;
+        for value in scene_data.probes.iter().flat_map(|p| &p.reading) {
+            write!(self.writer, ",{}", value)?;
+        }
+        writeln!(self.writer)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,196 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub const PROFILE_WINDOW: Duration = Duration::from_secs(1); // Timings are summarised over this long
+
+/// Timings of one phase over a window
+#[derive(Clone, Copy, Debug)]
+pub struct PhaseStats {
+    pub count: u32,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl PhaseStats {
+    fn new() -> Self {
+        PhaseStats {
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.total / self.count.max(1)
+    }
+}
+
+/// A complete event in the Chrome `trace_event` format, with times in microseconds
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+/// Streams timed scopes to a JSON file that chrome://tracing and Perfetto can open
+struct TraceWriter {
+    writer: BufWriter<File>,
+    events: usize,
+}
+
+/// Times named phases, e.g. the stages of a physics step, and summarises them over each
+/// `PROFILE_WINDOW`. Phases are listed in the order they were first timed.
+pub struct Profiler {
+    start: Instant,
+    window_start: Instant,
+    window: Vec<(&'static str, PhaseStats)>,
+    last_window: Vec<(&'static str, PhaseStats)>,
+    totals: Vec<(&'static str, PhaseStats)>,
+    trace: Option<TraceWriter>,
+    /// Why the trace stopped being written, reported by `finish`
+    trace_error: Option<io::Error>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        let now = Instant::now();
+        Profiler {
+            start: now,
+            window_start: now,
+            window: Vec::new(),
+            last_window: Vec::new(),
+            totals: Vec::new(),
+            trace: None,
+            trace_error: None,
+        }
+    }
+}
+
+impl Profiler {
+    /// Also writes every timed scope to a Chrome trace at `path`
+    pub fn with_trace(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "{{\"traceEvents\":[")?;
+        self.trace = Some(TraceWriter { writer, events: 0 });
+        Ok(self)
+    }
+
+    /// Runs `f`, timing it as `name`
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start);
+        result
+    }
+
+    /// Records a phase that started at `start` and ends now, for code that doesn't fit in a closure
+    pub fn record(&mut self, name: &'static str, start: Instant) {
+        let duration = start.elapsed();
+        for stats in [&mut self.window, &mut self.totals] {
+            match stats.iter_mut().find(|(phase, _)| *phase == name) {
+                Some((_, phase_stats)) => phase_stats.add(duration),
+                None => {
+                    let mut phase_stats = PhaseStats::new();
+                    phase_stats.add(duration);
+                    stats.push((name, phase_stats));
+                }
+            }
+        }
+
+        if let Some(trace) = &mut self.trace {
+            let event = TraceEvent {
+                name,
+                ph: "X",
+                ts: (start - self.start).as_secs_f64() * 1e6,
+                dur: duration.as_secs_f64() * 1e6,
+                pid: 1,
+                tid: 1,
+            };
+            let result = (if trace.events > 0 { trace.writer.write_all(b",") } else { Ok(()) })
+                .and_then(|_| serde_json::to_writer(&mut trace.writer, &event).map_err(io::Error::from));
+            match result {
+                Ok(()) => trace.events += 1,
+                Err(e) => {
+                    self.trace = None;
+                    self.trace_error = Some(e);
+                }
+            }
+        }
+    }
+
+    /// Call once per frame, moves the current window into `last_window` once it's complete
+    pub fn tick(&mut self) {
+        if self.window_start.elapsed() >= PROFILE_WINDOW {
+            self.last_window = std::mem::take(&mut self.window);
+            self.window_start = Instant::now();
+        }
+    }
+
+    /// Stats from the last complete window
+    pub fn last_window(&self) -> &[(&'static str, PhaseStats)] {
+        &self.last_window
+    }
+
+    /// Stats over everything timed so far
+    pub fn totals(&self) -> &[(&'static str, PhaseStats)] {
+        &self.totals
+    }
+
+    /// One line per phase with its min, mean and max time in milliseconds
+    pub fn summary(stats: &[(&'static str, PhaseStats)]) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+        let width = stats.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        stats
+            .iter()
+            .map(|(name, s)| {
+                format!(
+                    "{:width$} {:7.3} {:7.3} {:7.3}",
+                    name,
+                    ms(s.min),
+                    ms(s.mean()),
+                    ms(s.max),
+                    width = width
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Closes the trace file, which is invalid JSON until this is called. Fails with the error
+    /// that stopped the trace if writing it failed partway through.
+    pub fn finish(self) -> io::Result<()> {
+        if let Some(e) = self.trace_error {
+            return Err(e);
+        }
+        if let Some(mut trace) = self.trace {
+            write!(trace.writer, "]}}")?;
+            trace.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f`, timing it as `name` if there's a profiler. Without one the clock isn't read at all,
+/// so unprofiled runs don't pay for timing that would be thrown away.
+pub fn time_stage<T>(profiler: &mut Option<&mut Profiler>, name: &'static str, f: impl FnOnce() -> T) -> T {
+    match profiler {
+        Some(profiler) => profiler.time(name, f),
+        None => f(),
+    }
+}
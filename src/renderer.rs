@@ -1,13 +1,8 @@
-use crate::math::world_to_screen;
-use crate::scene_data::SceneData;
 use crate::sdl2_interface::SDL2Data;
-use cgmath::{InnerSpace, Vector2};
-use sdl2::gfx::primitives::DrawRenderer;
-use sdl2::pixels::Color;
-use crate::PARTICLE_COUNT;
+use fluid::scene_data::SceneData;
 
-pub fn render_scene_data<const C: usize>(scene_data: &SceneData<C>, sdl2_data: &mut SDL2Data) {
-    for particle in &scene_data.particles {
+pub fn render_scene_data(scene_data: &SceneData, _sdl2_data: &mut SDL2Data) {
+    for _particle in &scene_data.particles {
         // let pos = world_to_screen(particle.pos);
         //
         // let mut vel = particle.vel.magnitude();
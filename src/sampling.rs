@@ -0,0 +1,162 @@
+use crate::config::Geometry;
+use crate::kernels::poly6;
+use crate::physics::{axis_images, ring_density, ring_radius, INTERACTION_RADIUS};
+use crate::scene_data::SceneData;
+use crate::{Fp, WORLD_HEIGHT, WORLD_WIDTH};
+use cgmath::{Vector2, Zero};
+use serde::Serialize;
+
+/// A regular grid of sample points at the centres of `columns` x `rows` cells covering a rectangle
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct GridSpec {
+    pub min: Vector2<Fp>,
+    pub max: Vector2<Fp>,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl GridSpec {
+    /// Covers the whole world
+    pub fn world(columns: usize, rows: usize) -> Self {
+        GridSpec {
+            min: Vector2::zero(),
+            max: Vector2::new(WORLD_WIDTH, WORLD_HEIGHT),
+            columns,
+            rows,
+        }
+    }
+
+    pub fn cell_size(&self) -> Vector2<Fp> {
+        let size = self.max - self.min;
+        Vector2::new(size.x / self.columns as Fp, size.y / self.rows as Fp)
+    }
+
+    /// Position of the sample in `column`, `row`, with row 0 along `min.y`
+    pub fn point(&self, column: usize, row: usize) -> Vector2<Fp> {
+        let cell = self.cell_size();
+        self.min + Vector2::new((column as Fp + 0.5) * cell.x, (row as Fp + 0.5) * cell.y)
+    }
+
+    /// Parses `<columns>x<rows>`, e.g. `64x64`, covering the whole world
+    pub fn from_str_world(text: &str) -> Option<Self> {
+        let (columns, rows) = text.split_once('x')?;
+        Some(GridSpec::world(columns.parse().ok()?, rows.parse().ok()?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Fields sampled at each point of a grid, stored row by row starting from `min.y`
+pub struct GridFields {
+    pub spec: GridSpec,
+    pub density: Vec<Fp>,
+    pub velocity: Vec<Vector2<Fp>>,
+    pub pressure: Vec<Fp>,
+    pub vorticity: Vec<Fp>,
+}
+
+impl GridFields {
+    pub fn index(&self, column: usize, row: usize) -> usize {
+        row * self.spec.columns + column
+    }
+}
+
+/// Interpolates particle quantities onto a grid with the SPH kernels. Finds particles with the
+/// scene's neighbour grid, which physics steps leave matching the particles.
+pub struct GridSampler {
+    pub spec: GridSpec,
+}
+
+impl GridSampler {
+    pub fn new(spec: GridSpec) -> Self {
+        GridSampler { spec }
+    }
+
+    /// Samples every grid point, see `sample_point`
+    pub fn sample(&self, scene_data: &SceneData) -> GridFields {
+        let len = self.spec.len();
+        let mut fields = GridFields {
+            spec: self.spec,
+            density: Vec::with_capacity(len),
+            velocity: Vec::with_capacity(len),
+            pressure: Vec::with_capacity(len),
+            vorticity: Vec::with_capacity(len),
+        };
+
+        for row in 0..self.spec.rows {
+            for column in 0..self.spec.columns {
+                let sample = sample_point(scene_data, self.spec.point(column, row));
+                fields.density.push(sample.density);
+                fields.velocity.push(sample.velocity);
+                fields.pressure.push(sample.pressure);
+                fields.vorticity.push(sample.vorticity);
+            }
+        }
+        fields
+    }
+}
+
+/// Particle quantities interpolated at a point
+pub struct PointSample {
+    pub density: Fp,
+    pub velocity: Vector2<Fp>,
+    pub pressure: Fp,
+    pub vorticity: Fp,
+}
+
+/// Density is the plain kernel sum, or the ring density `compute_densities` gives particles in an
+/// axisymmetric world. Velocity, pressure and vorticity are normalised by the kernel weight
+/// (Shepard interpolation) so they don't fade towards the free surface, and are zero where there
+/// are no particles. Rings are weighted by their radius, with their mirror images across the axis
+/// moving and turning the opposite way. The scene's neighbour grid must match its particles.
+pub fn sample_point(scene_data: &SceneData, point: Vector2<Fp>) -> PointSample {
+    let (grid, particles) = (&scene_data.neighbour_grid, &scene_data.particles);
+    let axisymmetric = scene_data.geometry == Geometry::Axisymmetric;
+    let mut density = 0.0;
+    let mut weight_sum = 0.0;
+    let mut velocity = Vector2::zero();
+    let mut pressure = 0.0;
+    let mut vorticity = 0.0;
+    grid.for_each_candidate(point, |j| {
+        let pos_j = particles.pos(j);
+        let images = axis_images(point, pos_j, INTERACTION_RADIUS).take(if axisymmetric { 2 } else { 1 });
+        for (image, other) in images.enumerate() {
+            let kernel = poly6(grid.displacement(point, other));
+            if kernel == 0.0 {
+                continue;
+            }
+            density += particles.mass[j] * kernel;
+            if particles.density[j] > 0.0 {
+                let mass = if axisymmetric { particles.mass[j] * ring_radius(pos_j.x) } else { particles.mass[j] };
+                let weight = mass / particles.density[j] * kernel;
+                let sign = if image == 0 { 1.0 } else { -1.0 };
+                let vel = particles.vel(j);
+                weight_sum += weight;
+                velocity += Vector2::new(sign * vel.x, vel.y) * weight;
+                pressure += particles.pressure[j] * weight;
+                vorticity += sign * particles.vorticity[j] * weight;
+            }
+        }
+    });
+
+    if axisymmetric {
+        density = ring_density(grid, particles, point);
+    }
+    if weight_sum > 0.0 {
+        velocity /= weight_sum;
+        pressure /= weight_sum;
+        vorticity /= weight_sum;
+    }
+    PointSample {
+        density,
+        velocity,
+        pressure,
+        vorticity,
+    }
+}
@@ -1,41 +1,170 @@
+use crate::boundary::{KinematicBoundary, Motion};
+use crate::config::{BoundaryCondition, Geometry, SimulationConfig};
+use crate::emitter::{Drain, Emitter};
+use crate::force_field::ForceField;
+use crate::material::Material;
 use crate::math::screen_to_world;
-use crate::particle::Particle;
-use crate::{Fp, SCREEN_HEIGHT, SCREEN_WIDTH};
-use array_init::array_init;
+use crate::neighbour_grid::NeighbourGrid;
+use crate::particle::{Particle, Particles};
+use crate::physics::INTERACTION_RADIUS;
+use crate::probe::Probe;
+use crate::{Fp, SCREEN_HEIGHT, SCREEN_WIDTH, WORLD_HEIGHT, WORLD_WIDTH};
 use cgmath::Vector2;
-use rand::rngs::ThreadRng;
-use rand::{thread_rng, Rng};
-use std::cell::RefCell;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 pub enum SpawningMethod {
     Random,
+    /// Evenly spaced lattice filling a rectangle
+    Block {
+        min: Vector2<Fp>,
+        max: Vector2<Fp>,
+    },
 }
 
 impl SpawningMethod {
-    pub fn get_particles<const PARTICLE_COUNT: usize>(&self) -> [Particle; PARTICLE_COUNT] {
-        let mut rng = thread_rng();
-        array_init(|_| {
-            Particle::new(
-                screen_to_world((
-                    rng.gen_range(0..SCREEN_WIDTH),
-                    rng.gen_range(0..SCREEN_HEIGHT),
-                )),
-                1.0,
-            )
-        })
+    pub fn get_particles(&self, particle_count: usize, rng: &mut impl Rng) -> Particles {
+        match self {
+            SpawningMethod::Random => {
+                (0..particle_count)
+                    .map(|_| {
+                        Particle::new(
+                            screen_to_world((
+                                rng.gen_range(0..SCREEN_WIDTH),
+                                rng.gen_range(0..SCREEN_HEIGHT),
+                            )),
+                            1.0,
+                        )
+                    })
+                    .collect()
+            }
+            SpawningMethod::Block { min, max } => {
+                let size = max - min;
+                let spacing = (size.x * size.y / particle_count as Fp).sqrt();
+                let columns = ((size.x / spacing).round() as usize).max(1);
+                (0..particle_count)
+                    .map(|i| {
+                        let (column, row) = (i % columns, i / columns);
+                        let offset = Vector2::new(column as Fp + 0.5, row as Fp + 0.5) * spacing;
+                        Particle::new(min + offset, 1.0)
+                    })
+                    .collect()
+            }
+        }
     }
 }
 
-pub struct SceneData<const C: usize> {
-    pub particles: [Particle; C],
-    pub rng: ThreadRng,
+pub struct SceneData {
+    pub particles: Particles,
+    pub emitters: Vec<Emitter>,
+    pub drains: Vec<Drain>,
+    pub boundaries: Vec<KinematicBoundary>,
+    pub force_fields: Vec<Box<dyn ForceField>>,
+    /// The tank's walls span from its offset to `size` past it, the world unless set otherwise
+    pub size: Vector2<Fp>,
+    /// Movement of the world walls, e.g. to slosh the whole tank
+    pub tank_motion: Motion,
+    /// Whether the world is a flat slice or a cross-section of an axisymmetric flow
+    pub geometry: Geometry,
+    /// Built from the particles as every physics step leaves them, see `rebuild_neighbour_grid`
+    pub neighbour_grid: NeighbourGrid,
+    pub config: SimulationConfig,
+    /// Seeded so a scene built from the same seed always plays out the same way
+    pub rng: ChaCha8Rng,
+    pub time: Fp,
+    /// Sensors read every step, see `record_probes`
+    pub probes: Vec<Probe>,
+    /// Time of the probes' latest readings
+    pub probe_time: Fp,
 }
 
-impl<const C: usize> SceneData<C> {
-    pub fn new(particle_spawning_method: SpawningMethod) -> SceneData<C> {
+impl SceneData {
+    pub fn new(particle_spawning_method: SpawningMethod, particle_count: usize, seed: u64) -> SceneData {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
         SceneData {
-            particles: particle_spawning_method.get_particles(),
-            rng: thread_rng(),
+            particles: particle_spawning_method.get_particles(particle_count, &mut rng),
+            emitters: Vec::new(),
+            drains: Vec::new(),
+            boundaries: Vec::new(),
+            force_fields: Vec::new(),
+            size: Vector2::new(WORLD_WIDTH, WORLD_HEIGHT),
+            tank_motion: Motion::Static,
+            geometry: Geometry::Planar,
+            neighbour_grid: NeighbourGrid::new(INTERACTION_RADIUS),
+            config: SimulationConfig::default(),
+            rng,
+            time: 0.0,
+            probes: Vec::new(),
+            probe_time: 0.0,
+        }
+    }
+
+    pub fn with_emitter(mut self, emitter: Emitter) -> Self {
+        self.emitters.push(emitter);
+        self
+    }
+
+    pub fn with_drain(mut self, drain: Drain) -> Self {
+        self.drains.push(drain);
+        self
+    }
+
+    pub fn with_boundary(mut self, boundary: KinematicBoundary) -> Self {
+        self.boundaries.push(boundary);
+        self
+    }
+
+    pub fn with_force_field(mut self, force_field: impl ForceField + 'static) -> Self {
+        self.force_fields.push(Box::new(force_field));
+        self
+    }
+
+    pub fn with_probe(mut self, probe: Probe) -> Self {
+        self.probes.push(probe);
+        self
+    }
+
+    /// Resizes the tank and the neighbour grid with it, e.g. for a tank bigger than the screen
+    pub fn with_size(mut self, size: Vector2<Fp>) -> Self {
+        self.size = size;
+        self.neighbour_grid = NeighbourGrid::covering(INTERACTION_RADIUS, size);
+        self
+    }
+
+    pub fn with_tank_motion(mut self, tank_motion: Motion) -> Self {
+        self.tank_motion = tank_motion;
+        self
+    }
+
+    pub fn with_geometry(mut self, geometry: Geometry) -> Self {
+        self.geometry = geometry;
+        self
+    }
+
+    /// Re-buckets the particles in the neighbour grid. Physics steps do this as they finish, so
+    /// only code that moves, adds or removes particles between steps needs to call it.
+    pub fn rebuild_neighbour_grid(&mut self) {
+        let periodic = [self.config.boundary_x, self.config.boundary_y].map(|b| b == BoundaryCondition::Periodic);
+        self.neighbour_grid.rebuild(&self.particles, &periodic);
+    }
+
+    /// Changes the material of a particle, e.g. when it crosses a phase threshold
+    pub fn set_material(&mut self, index: usize, material: Material) {
+        let mut particle = self.particles.get_mut(index);
+        if material.is_solid() && !particle.material.is_solid() {
+            // Freezing particles lose some momentum so solids form rather than drift apart
+            particle.vel /= 2.0;
         }
+        particle.material = material;
+    }
+
+    pub fn toggle_emitters(&mut self) {
+        let enabled = !self.emitters.iter().any(|e| e.enabled);
+        self.emitters.iter_mut().for_each(|e| e.enabled = enabled);
+    }
+
+    pub fn toggle_drains(&mut self) {
+        let enabled = !self.drains.iter().any(|d| d.enabled);
+        self.drains.iter_mut().for_each(|d| d.enabled = enabled);
     }
 }
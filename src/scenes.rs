@@ -0,0 +1,227 @@
+use crate::boundary::{KinematicBoundary, Motion};
+use crate::config::{BoundaryCondition, Geometry, Gravity, GravitySource};
+use crate::emitter::{Drain, Emitter};
+use crate::force_field::{RegionField, TurbulenceField, VortexField};
+use crate::material::Material;
+use crate::probe::{Probe, ProbeShape};
+use crate::scene_data::{SceneData, SpawningMethod};
+use crate::{Fp, PARTICLE_COUNT, WORLD_HEIGHT, WORLD_WIDTH};
+use cgmath::num_traits::FloatConst;
+use cgmath::Vector2;
+
+/// Preset starting setups selectable from the command line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scene {
+    /// Particles scattered randomly over the whole world
+    Random,
+    /// Random particles boiling on a hot floor, freezing on a cold floor and condensing on the ceiling
+    PhaseChange,
+    /// An empty tank filled by a tap in the top left and emptied by a plug hole in the bottom right
+    Tap,
+    /// Random particles in a tank whose side walls wrap around to each other
+    Periodic,
+    /// A piston on the left pushing waves along a shallow tank
+    WaveTank,
+    /// A paddle spinning in the middle of a half full tank
+    Paddle,
+    /// A half full tank shaken from side to side
+    Sloshing,
+    /// Particles pulled towards a point in the middle of the world
+    Planet,
+    /// A half full tank stirred by a vortex, with turbulence near the surface
+    Whirlpool,
+    /// A jet falling down the axis of an axisymmetric tank onto a shallow pool, spreading out
+    /// over the floor and draining at the outer edge
+    Jet,
+}
+
+impl Scene {
+    pub const ALL: [Scene; 10] = [
+        Scene::Random,
+        Scene::PhaseChange,
+        Scene::Tap,
+        Scene::Periodic,
+        Scene::WaveTank,
+        Scene::Paddle,
+        Scene::Sloshing,
+        Scene::Planet,
+        Scene::Whirlpool,
+        Scene::Jet,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Scene> {
+        match name {
+            "random" => Some(Scene::Random),
+            "phase_change" => Some(Scene::PhaseChange),
+            "tap" => Some(Scene::Tap),
+            "periodic" => Some(Scene::Periodic),
+            "wave_tank" => Some(Scene::WaveTank),
+            "paddle" => Some(Scene::Paddle),
+            "sloshing" => Some(Scene::Sloshing),
+            "planet" => Some(Scene::Planet),
+            "whirlpool" => Some(Scene::Whirlpool),
+            "jet" => Some(Scene::Jet),
+            _ => None,
+        }
+    }
+
+    /// The name `from_name` accepts for this scene
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::Random => "random",
+            Scene::PhaseChange => "phase_change",
+            Scene::Tap => "tap",
+            Scene::Periodic => "periodic",
+            Scene::WaveTank => "wave_tank",
+            Scene::Paddle => "paddle",
+            Scene::Sloshing => "sloshing",
+            Scene::Planet => "planet",
+            Scene::Whirlpool => "whirlpool",
+            Scene::Jet => "jet",
+        }
+    }
+
+    /// Builds the scene, with `seed` deciding random particle positions and anything else random
+    pub fn build(&self, seed: u64) -> SceneData {
+        match self {
+            Scene::Random => SceneData::new(SpawningMethod::Random, PARTICLE_COUNT, seed),
+            Scene::PhaseChange => {
+                let mut scene_data = SceneData::new(SpawningMethod::Random, PARTICLE_COUNT, seed);
+                scene_data.config.wall_heat_transfer = true;
+                scene_data
+            }
+            Scene::Tap => SceneData::new(SpawningMethod::Random, 0, seed)
+                .with_emitter(Emitter::new(
+                    Vector2::new(0.1, 0.9),
+                    Vector2::new(1.0, -0.2),
+                    150.0,
+                    1.5,
+                    Material::Water,
+                    0.04,
+                ))
+                .with_drain(Drain::new(
+                    Vector2::new(WORLD_WIDTH - 0.15, 0.0),
+                    Vector2::new(WORLD_WIDTH - 0.05, 0.04),
+                )),
+            Scene::Periodic => {
+                let mut scene_data = SceneData::new(SpawningMethod::Random, PARTICLE_COUNT, seed);
+                scene_data.config.boundary_x = BoundaryCondition::Periodic;
+                scene_data
+            }
+            Scene::WaveTank => SceneData::new(
+                SpawningMethod::Block {
+                    min: Vector2::new(0.15, 0.0),
+                    max: Vector2::new(WORLD_WIDTH, 0.35),
+                },
+                PARTICLE_COUNT,
+                seed,
+            )
+            .with_boundary(KinematicBoundary::new(
+                Vector2::new(0.1, 0.3),
+                0.6,
+                Fp::FRAC_PI_2(),
+                Motion::Oscillate {
+                    amplitude: Vector2::new(0.06, 0.0),
+                    period: 1.5,
+                },
+            ))
+            .with_probe(Probe::new(
+                "gauge_near",
+                ProbeShape::Line {
+                    start: Vector2::new(0.4, 0.0),
+                    end: Vector2::new(0.4, 0.7),
+                },
+            ))
+            .with_probe(Probe::new(
+                "gauge_far",
+                ProbeShape::Line {
+                    start: Vector2::new(0.8, 0.0),
+                    end: Vector2::new(0.8, 0.7),
+                },
+            )),
+            Scene::Paddle => SceneData::new(
+                SpawningMethod::Block {
+                    min: Vector2::new(0.0, 0.0),
+                    max: Vector2::new(WORLD_WIDTH, 0.5),
+                },
+                PARTICLE_COUNT,
+                seed,
+            )
+            .with_boundary(KinematicBoundary::new(
+                Vector2::new(WORLD_WIDTH / 2.0, 0.15),
+                0.4,
+                0.0,
+                Motion::Rotate {
+                    angular_velocity: 2.0,
+                },
+            )),
+            Scene::Sloshing => SceneData::new(
+                SpawningMethod::Block {
+                    min: Vector2::new(0.0, 0.0),
+                    max: Vector2::new(WORLD_WIDTH, 0.4),
+                },
+                PARTICLE_COUNT,
+                seed,
+            )
+            .with_tank_motion(Motion::Oscillate {
+                amplitude: Vector2::new(0.04, 0.0),
+                period: 1.2,
+            })
+            .with_probe(Probe::new("left_wall", ProbeShape::Point(Vector2::new(0.02, 0.1))))
+            .with_probe(Probe::new("right_wall", ProbeShape::Point(Vector2::new(WORLD_WIDTH - 0.02, 0.1)))),
+            Scene::Planet => {
+                let mut scene_data = SceneData::new(SpawningMethod::Random, PARTICLE_COUNT, seed);
+                scene_data.config.gravity = Gravity::Sources(vec![GravitySource {
+                    pos: Vector2::new(WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0),
+                    strength: 0.15,
+                    inverse_square: true,
+                }]);
+                scene_data
+            }
+            Scene::Whirlpool => SceneData::new(
+                SpawningMethod::Block {
+                    min: Vector2::new(0.0, 0.0),
+                    max: Vector2::new(WORLD_WIDTH, 0.5),
+                },
+                PARTICLE_COUNT,
+                seed,
+            )
+            .with_force_field(VortexField {
+                centre: Vector2::new(WORLD_WIDTH / 2.0, 0.15),
+                strength: 15.0,
+                radius: 0.3,
+            })
+            .with_force_field(RegionField {
+                min: Vector2::new(0.0, 0.15),
+                max: Vector2::new(WORLD_WIDTH, WORLD_HEIGHT),
+                field: Box::new(TurbulenceField {
+                    strength: 5.0,
+                    scale: 0.1,
+                    frequency: 1.0,
+                    seed: 0,
+                }),
+            }),
+            Scene::Jet => SceneData::new(
+                SpawningMethod::Block {
+                    min: Vector2::new(0.0, 0.0),
+                    max: Vector2::new(WORLD_WIDTH, 0.1),
+                },
+                PARTICLE_COUNT / 4,
+                seed,
+            )
+            .with_geometry(Geometry::Axisymmetric)
+            .with_emitter(Emitter::new(
+                Vector2::new(0.025, 0.6),
+                Vector2::new(0.0, -1.0),
+                150.0,
+                1.0,
+                Material::Water,
+                0.04,
+            ))
+            .with_drain(Drain::new(
+                Vector2::new(WORLD_WIDTH - 0.05, 0.0),
+                Vector2::new(WORLD_WIDTH, WORLD_HEIGHT),
+            )),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use fluid::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use sdl2::render::WindowCanvas;
 use sdl2::{EventPump, TimerSubsystem};
 use crate::opengl_interface::{init_opengl, ShaderProgram};
@@ -12,7 +12,7 @@ pub struct SDL2Data {
 
 pub fn init_sdl2() -> SDL2Data {
     let sdl_context = sdl2::init().expect("SDL2 failed to load");
-    let mut event_pump = sdl_context.event_pump().expect("Failed to get event pump");
+    let event_pump = sdl_context.event_pump().expect("Failed to get event pump");
     let timer_subsystem = sdl_context.timer().unwrap();
 
     let video = sdl_context.video().expect("Failed to get SDL video");
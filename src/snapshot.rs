@@ -0,0 +1,158 @@
+use crate::boundary::{KinematicBoundary, Motion};
+use crate::config::SimulationConfig;
+use crate::emitter::{Drain, Emitter};
+use crate::particle::Particles;
+use crate::scene_data::SceneData;
+use crate::scenes::Scene;
+use crate::Fp;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// Binary snapshots store `Fp`, so each precision has its own magic
+const MAGIC_F32: &[u8; 4] = b"FLSN";
+const MAGIC_F64: &[u8; 4] = b"FLSD";
+const MAGIC: &[u8; 4] = if cfg!(feature = "f64") { MAGIC_F64 } else { MAGIC_F32 };
+const VERSION: u32 = 1;
+
+/// Everything needed to resume a simulation exactly where it left off. Force fields and the
+/// geometry aren't stored, they're rebuilt from the scene preset the snapshot was taken from.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub scene: String,
+    pub time: Fp,
+    pub rng: ChaCha8Rng,
+    pub config: SimulationConfig,
+    pub particles: Particles,
+    pub emitters: Vec<Emitter>,
+    pub drains: Vec<Drain>,
+    pub boundaries: Vec<KinematicBoundary>,
+    pub tank_motion: Motion,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Binary(bincode::Error),
+    Text(serde_json::Error),
+    /// The file doesn't start with the snapshot magic bytes
+    NotASnapshot,
+    /// A binary snapshot saved by a build with the other floating point precision
+    WrongPrecision,
+    UnsupportedVersion(u32),
+    UnknownScene(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::Binary(e) => write!(f, "invalid binary snapshot: {}", e),
+            SnapshotError::Text(e) => write!(f, "invalid text snapshot: {}", e),
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot file"),
+            SnapshotError::WrongPrecision => {
+                write!(f, "saved with the other floating point precision, toggle the f64 feature to load it")
+            }
+            SnapshotError::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {}", v),
+            SnapshotError::UnknownScene(name) => write!(f, "unknown scene '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotError::Binary(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Text(e)
+    }
+}
+
+impl Snapshot {
+    pub fn capture(scene: Scene, scene_data: &SceneData) -> Self {
+        Snapshot {
+            version: VERSION,
+            scene: scene.name().to_string(),
+            time: scene_data.time,
+            rng: scene_data.rng.clone(),
+            config: scene_data.config.clone(),
+            particles: scene_data.particles.clone(),
+            emitters: scene_data.emitters.clone(),
+            drains: scene_data.drains.clone(),
+            boundaries: scene_data.boundaries.clone(),
+            tank_motion: scene_data.tank_motion.clone(),
+        }
+    }
+
+    /// Rebuilds the scene the snapshot was taken from and overwrites its state with the snapshot's
+    pub fn restore(self) -> Result<(Scene, SceneData), SnapshotError> {
+        let scene = Scene::from_name(&self.scene).ok_or(SnapshotError::UnknownScene(self.scene))?;
+        let mut scene_data = scene.build(0);
+        scene_data.time = self.time;
+        scene_data.rng = self.rng;
+        scene_data.config = self.config;
+        scene_data.particles = self.particles;
+        scene_data.emitters = self.emitters;
+        scene_data.drains = self.drains;
+        scene_data.boundaries = self.boundaries;
+        scene_data.tank_motion = self.tank_motion;
+        scene_data.rebuild_neighbour_grid();
+        Ok((scene, scene_data))
+    }
+
+    /// Writes the snapshot as JSON if `path` ends in `.json`, otherwise in the compact binary format
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+        if is_text(path) {
+            serde_json::to_writer_pretty(&mut writer, self)?;
+        } else {
+            writer.write_all(MAGIC)?;
+            bincode::serialize_into(&mut writer, self)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = if is_text(path) {
+            serde_json::from_reader(reader)?
+        } else {
+            let mut magic = [0; 4];
+            reader.read_exact(&mut magic)?;
+            if &magic != MAGIC {
+                return Err(if [MAGIC_F32, MAGIC_F64].contains(&&magic) {
+                    SnapshotError::WrongPrecision
+                } else {
+                    SnapshotError::NotASnapshot
+                });
+            }
+            bincode::deserialize_from(reader)?
+        };
+        if snapshot.version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+}
+
+fn is_text(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+}
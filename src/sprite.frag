@@ -0,0 +1,27 @@
+#version 450 core
+
+uniform bool Sprites; // Points are shaded as spheres, lines are drawn flat
+
+in VS_OUTPUT {
+    vec3 Color;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    if (!Sprites) {
+        Color = vec4(IN.Color, 1.0f);
+        return;
+    }
+
+    vec2 offset = gl_PointCoord * 2.0 - 1.0;
+    float distance_sq = dot(offset, offset);
+    if (distance_sq > 1.0) {
+        discard;
+    }
+    // Lit from the upper left, as if each sprite were a sphere facing the camera
+    vec3 normal = vec3(offset.x, -offset.y, sqrt(1.0 - distance_sq));
+    float light = 0.3 + 0.7 * max(dot(normal, normalize(vec3(-0.4, 0.6, 0.7))), 0.0);
+    Color = vec4(IN.Color * light, 1.0f);
+}
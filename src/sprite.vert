@@ -0,0 +1,18 @@
+#version 450 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec3 Color;
+
+uniform mat4 ViewProjection;
+uniform float PointScale; // Sprite diameter in pixels one unit in front of the camera
+
+out VS_OUTPUT {
+    vec3 Color;
+} OUT;
+
+void main()
+{
+    gl_Position = ViewProjection * vec4(Position, 1.0);
+    gl_PointSize = PointScale / gl_Position.w;
+    OUT.Color = Color;
+}
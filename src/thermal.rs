@@ -0,0 +1,78 @@
+use crate::physics::INTERACTION_RADIUS;
+use crate::scene_data::SceneData;
+use crate::{Fp, WORLD_HEIGHT, WORLD_WIDTH};
+use cgmath::InnerSpace;
+
+// Temperatures are in degrees Celsius
+pub const AMBIENT_TEMPERATURE: Fp = 20.0;
+pub const HOT_FLOOR_TEMPERATURE: Fp = 130.0;
+pub const COLD_FLOOR_TEMPERATURE: Fp = -60.0;
+pub const CEILING_TEMPERATURE: Fp = 0.0;
+
+const CONDUCTIVITY: Fp = 0.5; // Rate particles relax towards their neighbours' temperature
+const WALL_CONDUCTIVITY: Fp = 4.0;
+const WALL_HEAT_DISTANCE: Fp = 0.06;
+
+/// Diffuses heat between nearby particles and between particles and the walls. The floor is heated
+/// on the left half and cooled on the right half, and the ceiling is cool enough to condense steam.
+pub fn apply_heat_transfer(scene_data: &mut SceneData, delta_time: Fp) {
+    let particles = &scene_data.particles;
+    let mut new_temperatures = Vec::with_capacity(scene_data.particles.len());
+
+    for particle in particles {
+        let mut weighted_sum = 0.0;
+        let mut weight_total = 0.0;
+        scene_data
+            .neighbour_grid
+            .for_each_candidate(particle.pos, |j| {
+                let dist = scene_data
+                    .neighbour_grid
+                    .displacement(particle.pos, particles.pos(j))
+                    .magnitude();
+                if dist > INTERACTION_RADIUS {
+                    return;
+                }
+                let weight = 1.0 - dist / INTERACTION_RADIUS;
+                weighted_sum += particles.temperature[j] * weight;
+                weight_total += weight;
+            });
+
+        let mut temperature = particle.temperature;
+        // Weight total is never zero as a particle is always its own neighbour
+        let neighbour_mean = weighted_sum / weight_total;
+        temperature += (neighbour_mean - temperature) * (CONDUCTIVITY * delta_time).min(1.0);
+
+        if !scene_data.config.wall_heat_transfer {
+            new_temperatures.push(temperature);
+            continue;
+        }
+
+        let wall_rate = (WALL_CONDUCTIVITY * delta_time).min(1.0);
+        if particle.pos.y < WALL_HEAT_DISTANCE {
+            let floor_temperature = if particle.pos.x < WORLD_WIDTH / 2.0 {
+                HOT_FLOOR_TEMPERATURE
+            } else {
+                COLD_FLOOR_TEMPERATURE
+            };
+            temperature += (floor_temperature - temperature) * wall_rate;
+        }
+        if particle.pos.y > WORLD_HEIGHT - WALL_HEAT_DISTANCE {
+            temperature += (CEILING_TEMPERATURE - temperature) * wall_rate;
+        }
+
+        new_temperatures.push(temperature);
+    }
+
+    scene_data.particles.temperature = new_temperatures;
+}
+
+/// Switches the material of any particle whose temperature has crossed a phase threshold
+pub fn apply_phase_changes(scene_data: &mut SceneData) {
+    for i in 0..scene_data.particles.len() {
+        let material = scene_data.particles.material[i];
+        let new_material = material.phase_at(scene_data.particles.temperature[i]);
+        if new_material != material {
+            scene_data.set_material(i, new_material);
+        }
+    }
+}
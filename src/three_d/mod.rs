@@ -0,0 +1,8 @@
+//! 3D simulation in a box, alongside the 2D one. It runs the same solver, pairwise repulsion
+//! with linear drag and SPH densities, on the same particle storage, neighbour grid, kernels and
+//! force fields, with 3D vectors. Scene features like emitters, probes, snapshots and exports
+//! are only available in 2D so far.
+
+pub mod physics;
+pub mod scene_data;
+pub mod scenes;
@@ -0,0 +1,106 @@
+use crate::config::BoundaryCondition;
+use crate::force_field::{Drag, ForceField, UniformField};
+use crate::particle::Particle;
+use crate::physics::{
+    bound_axis, get_force, integrate_motion, particle_densities, particle_repulsion, set_accels,
+};
+use crate::profiler::{time_stage, Profiler};
+use crate::three_d::scene_data::SceneData;
+use crate::Fp;
+use cgmath::{Vector3, Zero};
+use rayon::prelude::*;
+use std::time::Instant;
+
+pub use crate::physics::{GRAVITY, MAX_REPULSION_DIST};
+const PARTICLE_FORCE_SCALE: Fp = 0.003; // Stronger than 2D, where each particle has fewer neighbours
+const WALL_FORCE_SCALE: Fp = 0.03; // Keeps the gap to the walls about one particle spacing
+
+pub const REST_DENSITY: Fp = 35000.0; // Measured bulk density of the settled tank
+
+pub fn physics_update(scene_data: &mut SceneData, delta_time: Fp) {
+    step(scene_data, delta_time, None);
+}
+
+/// `physics_update`, timing each stage with `profiler`
+pub fn physics_update_profiled(scene_data: &mut SceneData, delta_time: Fp, profiler: &mut Profiler) {
+    step(scene_data, delta_time, Some(profiler));
+}
+
+/// One physics step, timing each stage if there's a profiler
+fn step(scene_data: &mut SceneData, delta_time: Fp, mut profiler: Option<&mut Profiler>) {
+    let step_start = profiler.is_some().then(Instant::now);
+    time_stage(&mut profiler, "neighbour_grid", || {
+        scene_data.neighbour_grid.rebuild(&scene_data.particles, &[])
+    });
+    time_stage(&mut profiler, "densities", || compute_densities(scene_data));
+    time_stage(&mut profiler, "force_fields", || apply_force_fields(scene_data));
+    time_stage(&mut profiler, "repulsion", || apply_repulsive_particle_force(scene_data));
+    time_stage(&mut profiler, "integration", || integrate_motion(&mut scene_data.particles, delta_time));
+    scene_data.time += delta_time;
+    time_stage(&mut profiler, "boundaries", || bound_particles(scene_data));
+    if let (Some(profiler), Some(step_start)) = (profiler, step_start) {
+        profiler.record("physics_update", step_start);
+    }
+}
+
+pub fn compute_densities(scene_data: &mut SceneData) {
+    scene_data.particles.density = particle_densities(&scene_data.neighbour_grid, &scene_data.particles);
+}
+
+/// Sets every particle's acceleration to that from gravity, drag and the box walls
+pub fn apply_force_fields(scene_data: &mut SceneData) {
+    let (time, gravity) = (scene_data.time, UniformField { accel: scene_data.gravity });
+    let drag = Drag {
+        coefficient: scene_data.drag_coefficient,
+    };
+    let walls = BoxWalls { size: scene_data.size };
+    let fields: [&dyn ForceField<Vector3<Fp>>; 3] = [&gravity, &drag, &walls];
+
+    let particles = &scene_data.particles;
+    let accels: Vec<Vector3<Fp>> = (0..particles.len())
+        .into_par_iter()
+        .map(|i| {
+            let particle = particles.get(i);
+            fields.iter().map(|field| field.acceleration(&particle, time)).sum()
+        })
+        .collect();
+    set_accels(&mut scene_data.particles, &accels);
+}
+
+/// Pushes particles away from the walls of a box from the origin to `size`
+pub struct BoxWalls {
+    pub size: Vector3<Fp>,
+}
+
+impl ForceField<Vector3<Fp>> for BoxWalls {
+    fn acceleration(&self, particle: &Particle<Vector3<Fp>>, _time: Fp) -> Vector3<Fp> {
+        let pos = particle.pos;
+        let mut total_force = Vector3::zero();
+        for axis in 0..3 {
+            // Walls sit just outside the box, like in 2D
+            let mut low = pos;
+            low[axis] = -0.01;
+            let mut high = pos;
+            high[axis] = self.size[axis] + 0.01;
+            total_force += get_force(pos, low, WALL_FORCE_SCALE) + get_force(pos, high, WALL_FORCE_SCALE);
+        }
+        total_force / particle.mass
+    }
+}
+
+/// Pushes nearby particles apart, and sets each particle's pressure from the forces it feels
+pub fn apply_repulsive_particle_force(scene_data: &mut SceneData) {
+    let (accels, pressures) =
+        particle_repulsion(&scene_data.neighbour_grid, &scene_data.particles, PARTICLE_FORCE_SCALE);
+    set_accels(&mut scene_data.particles, &accels);
+    scene_data.particles.pressure = pressures;
+}
+
+/// Bounces particles off the walls of the box
+pub fn bound_particles(scene_data: &mut SceneData) {
+    let size = scene_data.size;
+    let particles = &mut scene_data.particles;
+    for (axis, (pos, vel)) in particles.pos.iter_mut().zip(&mut particles.vel).enumerate() {
+        bound_axis(pos, vel, BoundaryCondition::Wall, (0.0, size[axis]), 0.0, size[axis]);
+    }
+}
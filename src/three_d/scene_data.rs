@@ -0,0 +1,69 @@
+use crate::physics::DRAG_COEF;
+use crate::neighbour_grid::NeighbourGrid;
+use crate::particle::{Particle, Particles};
+use crate::three_d::physics::{GRAVITY, MAX_REPULSION_DIST};
+use crate::Fp;
+use cgmath::{ElementWise, Vector3};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub enum SpawningMethod {
+    /// Scattered uniformly over the whole box
+    Random,
+    /// Evenly spaced lattice filling a cuboid
+    Block { min: Vector3<Fp>, max: Vector3<Fp> },
+}
+
+impl SpawningMethod {
+    pub fn get_particles(&self, size: Vector3<Fp>, particle_count: usize, rng: &mut impl Rng) -> Particles<Vector3<Fp>> {
+        match self {
+            SpawningMethod::Random => (0..particle_count)
+                .map(|_| {
+                    let fraction = Vector3::new(rng.gen::<Fp>(), rng.gen(), rng.gen());
+                    Particle::new(size.mul_element_wise(fraction), 1.0)
+                })
+                .collect(),
+            SpawningMethod::Block { min, max } => {
+                let extent = max - min;
+                let spacing = (extent.x * extent.y * extent.z / particle_count as Fp).cbrt();
+                let columns = ((extent.x / spacing).round() as usize).max(1);
+                let rows = ((extent.z / spacing).round() as usize).max(1);
+                (0..particle_count)
+                    .map(|i| {
+                        let (column, row, layer) = (i % columns, i / columns % rows, i / (columns * rows));
+                        let offset = Vector3::new(column as Fp + 0.5, layer as Fp + 0.5, row as Fp + 0.5) * spacing;
+                        Particle::new(min + offset, 1.0)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A 3D scene: particles in a box with walls on every side, with y up
+pub struct SceneData {
+    pub particles: Particles<Vector3<Fp>>,
+    /// The box spans from the origin to `size`
+    pub size: Vector3<Fp>,
+    pub neighbour_grid: NeighbourGrid<Vector3<Fp>>,
+    pub gravity: Vector3<Fp>,
+    pub drag_coefficient: Fp,
+    /// Seeded so a scene built from the same seed always plays out the same way
+    pub rng: ChaCha8Rng,
+    pub time: Fp,
+}
+
+impl SceneData {
+    pub fn new(size: Vector3<Fp>, particle_spawning_method: SpawningMethod, particle_count: usize, seed: u64) -> SceneData {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        SceneData {
+            particles: particle_spawning_method.get_particles(size, particle_count, &mut rng),
+            size,
+            neighbour_grid: NeighbourGrid::covering(MAX_REPULSION_DIST, size),
+            gravity: Vector3::new(0.0, GRAVITY, 0.0),
+            drag_coefficient: DRAG_COEF,
+            rng,
+            time: 0.0,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::three_d::scene_data::{SceneData, SpawningMethod};
+use crate::Fp;
+use cgmath::Vector3;
+
+pub const PARTICLE_COUNT: usize = 8000;
+
+/// Preset 3D starting setups, selected with `--scene` like the 2D ones
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scene {
+    /// A cube of water dropped into the middle of an empty tank
+    Drop,
+    /// A column of water at one end of a long tank, released at the start
+    DamBreak,
+    /// A tank a third full, settling from a loose lattice
+    Tank,
+}
+
+impl Scene {
+    pub const ALL: [Scene; 3] = [Scene::Drop, Scene::DamBreak, Scene::Tank];
+
+    pub fn from_name(name: &str) -> Option<Scene> {
+        match name {
+            "drop_3d" => Some(Scene::Drop),
+            "dam_break_3d" => Some(Scene::DamBreak),
+            "tank_3d" => Some(Scene::Tank),
+            _ => None,
+        }
+    }
+
+    /// The name `from_name` accepts for this scene
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::Drop => "drop_3d",
+            Scene::DamBreak => "dam_break_3d",
+            Scene::Tank => "tank_3d",
+        }
+    }
+
+    /// Builds the scene, with `seed` deciding anything random
+    pub fn build(&self, seed: u64) -> SceneData {
+        let block = |min: Vector3<Fp>, max: Vector3<Fp>| SpawningMethod::Block { min, max };
+        match self {
+            Scene::Drop => SceneData::new(
+                Vector3::new(1.0, 1.0, 1.0),
+                block(Vector3::new(0.3, 0.5, 0.3), Vector3::new(0.7, 0.9, 0.7)),
+                PARTICLE_COUNT,
+                seed,
+            ),
+            Scene::DamBreak => SceneData::new(
+                Vector3::new(1.6, 0.8, 0.6),
+                block(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.4, 0.6, 0.6)),
+                PARTICLE_COUNT,
+                seed,
+            ),
+            Scene::Tank => SceneData::new(
+                Vector3::new(1.0, 1.0, 1.0),
+                block(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.35, 1.0)),
+                PARTICLE_COUNT,
+                seed,
+            ),
+        }
+    }
+}
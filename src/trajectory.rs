@@ -0,0 +1,226 @@
+use crate::material::Material;
+use crate::particle::Particle;
+use crate::scene_data::SceneData;
+use crate::scenes::Scene;
+use crate::Fp;
+use cgmath::Vector2;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+// Frames store `Fp`, so each precision has its own magic
+const MAGIC_F32: &[u8; 4] = b"FLTR";
+const MAGIC_F64: &[u8; 4] = b"FLTD";
+const MAGIC: &[u8; 4] = if cfg!(feature = "f64") { MAGIC_F64 } else { MAGIC_F32 };
+const VERSION: u32 = 2;
+
+pub const DEFAULT_RECORD_INTERVAL: u32 = 5; // Steps between recorded frames
+
+#[derive(Serialize, Deserialize)]
+pub struct TrajectoryHeader {
+    pub version: u32,
+    pub scene: String,
+    /// Physics steps between frames
+    pub record_interval: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FrameParticle {
+    pub pos: Vector2<Fp>,
+    pub vel: Vector2<Fp>,
+    pub mass: Fp,
+    pub material: Material,
+}
+
+/// The particles at one point in a recorded run
+#[derive(Serialize, Deserialize)]
+pub struct Frame {
+    pub time: Fp,
+    pub particles: Vec<FrameParticle>,
+}
+
+impl Frame {
+    pub fn capture(scene_data: &SceneData) -> Self {
+        Frame {
+            time: scene_data.time,
+            particles: scene_data
+                .particles
+                .iter()
+                .map(|p| FrameParticle {
+                    pos: p.pos,
+                    vel: p.vel,
+                    mass: p.mass,
+                    material: p.material,
+                })
+                .collect(),
+        }
+    }
+
+    /// Replaces the scene's particles and time with the frame's so it can be drawn as normal
+    pub fn apply_to(&self, scene_data: &mut SceneData) {
+        scene_data.time = self.time;
+        scene_data.particles = self
+            .particles
+            .iter()
+            .map(|p| {
+                let mut particle = Particle::with_material(p.pos, p.mass, p.material);
+                particle.vel = p.vel;
+                particle
+            })
+            .collect();
+        scene_data.rebuild_neighbour_grid();
+    }
+}
+
+#[derive(Debug)]
+pub enum TrajectoryError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    /// The file doesn't start with the trajectory magic bytes
+    NotATrajectory,
+    /// Recorded by a build with the other floating point precision
+    WrongPrecision,
+    UnsupportedVersion(u32),
+    UnknownScene(String),
+}
+
+impl fmt::Display for TrajectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrajectoryError::Io(e) => write!(f, "{}", e),
+            TrajectoryError::Encoding(e) => write!(f, "invalid trajectory: {}", e),
+            TrajectoryError::NotATrajectory => write!(f, "not a trajectory file"),
+            TrajectoryError::WrongPrecision => {
+                write!(f, "recorded with the other floating point precision, toggle the f64 feature to load it")
+            }
+            TrajectoryError::UnsupportedVersion(v) => {
+                write!(f, "unsupported trajectory version {}", v)
+            }
+            TrajectoryError::UnknownScene(name) => write!(f, "unknown scene '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for TrajectoryError {}
+
+impl From<std::io::Error> for TrajectoryError {
+    fn from(e: std::io::Error) -> Self {
+        TrajectoryError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for TrajectoryError {
+    fn from(e: bincode::Error) -> Self {
+        TrajectoryError::Encoding(e)
+    }
+}
+
+/// Streams frames to a gzip compressed file as the simulation runs
+pub struct TrajectoryRecorder {
+    encoder: GzEncoder<BufWriter<File>>,
+    record_interval: u32,
+    steps: u64,
+}
+
+impl TrajectoryRecorder {
+    pub fn create(
+        path: impl AsRef<Path>,
+        scene: Scene,
+        record_interval: u32,
+    ) -> Result<Self, TrajectoryError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        let header = TrajectoryHeader {
+            version: VERSION,
+            scene: scene.name().to_string(),
+            record_interval: record_interval.max(1),
+        };
+        bincode::serialize_into(&mut encoder, &header)?;
+        Ok(TrajectoryRecorder {
+            encoder,
+            record_interval: header.record_interval,
+            steps: 0,
+        })
+    }
+
+    /// Call once per physics step, every `record_interval`th call writes a frame
+    pub fn record(&mut self, scene_data: &SceneData) -> Result<(), TrajectoryError> {
+        if self.steps.is_multiple_of(self.record_interval as u64) {
+            bincode::serialize_into(&mut self.encoder, &Frame::capture(scene_data))?;
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Flushes the remaining frames. Dropping the recorder also finishes the file but ignores errors.
+    pub fn finish(self) -> Result<(), TrajectoryError> {
+        self.encoder.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+/// A recorded run loaded into memory for replay
+pub struct Trajectory {
+    pub header: TrajectoryHeader,
+    pub frames: Vec<Frame>,
+}
+
+impl Trajectory {
+    /// Loads every frame, a run cut short (e.g. by a crash) loads up to its last complete frame
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrajectoryError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(if [MAGIC_F32, MAGIC_F64].contains(&&magic) {
+                TrajectoryError::WrongPrecision
+            } else {
+                TrajectoryError::NotATrajectory
+            });
+        }
+
+        let mut decoder = BufReader::new(GzDecoder::new(reader));
+        let header: TrajectoryHeader = bincode::deserialize_from(&mut decoder)?;
+        if header.version != VERSION {
+            return Err(TrajectoryError::UnsupportedVersion(header.version));
+        }
+
+        let mut frames = Vec::new();
+        loop {
+            match bincode::deserialize_from(&mut decoder) {
+                Ok(frame) => frames.push(frame),
+                Err(e) => match *e {
+                    bincode::ErrorKind::Io(ref io) if io.kind() == ErrorKind::UnexpectedEof => break,
+                    _ => return Err(e.into()),
+                },
+            }
+        }
+        Ok(Trajectory { header, frames })
+    }
+
+    pub fn scene(&self) -> Result<Scene, TrajectoryError> {
+        Scene::from_name(&self.header.scene)
+            .ok_or_else(|| TrajectoryError::UnknownScene(self.header.scene.clone()))
+    }
+
+    pub fn start_time(&self) -> Fp {
+        self.frames.first().map_or(0.0, |f| f.time)
+    }
+
+    pub fn end_time(&self) -> Fp {
+        self.frames.last().map_or(0.0, |f| f.time)
+    }
+
+    /// Index of the last frame at or before `time`
+    pub fn frame_index_at(&self, time: Fp) -> usize {
+        self.frames
+            .partition_point(|f| f.time <= time)
+            .saturating_sub(1)
+    }
+}
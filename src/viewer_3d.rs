@@ -0,0 +1,304 @@
+use crate::controls::{Action, KeyBindings, Overlay, Overlays};
+use crate::hud::text_quads;
+use crate::opengl_interface::sprite_shader_program;
+use crate::sdl2_interface::init_sdl2;
+use crate::{push_vertex, MAX_TIME_SCALE, MIN_TIME_SCALE, TIME_SCALE_STEP};
+use cgmath::{perspective, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Vector2, Vector3};
+use fluid::profiler::Profiler;
+use fluid::three_d::physics::{physics_update_profiled, REST_DENSITY};
+use fluid::three_d::scene_data::SceneData;
+use fluid::three_d::scenes::Scene;
+use fluid::{Fp, SCREEN_HEIGHT, SCREEN_WIDTH, WORLD_HEIGHT, WORLD_WIDTH};
+use gl::types::GLenum;
+use sdl2::event::Event;
+use std::time::Instant;
+
+pub const DELTA_TIME: Fp = 0.005; // 3D always steps at this rate, several times a frame if needed
+pub const MAX_STEPS_PER_FRAME: u32 = 8; // Slow frames run behind real time rather than spiral
+
+const FIELD_OF_VIEW: f32 = 45.0; // Vertical, in degrees
+const SPRITE_RADIUS: f32 = 0.012;
+const ORBIT_SPEED: f32 = 0.01; // Radians per pixel dragged
+const ZOOM_STEP: f32 = 1.1; // Distance multiplier per scroll wheel notch
+
+/// Circles a point, turned by dragging with the left mouse button and zoomed with the scroll wheel
+pub struct OrbitCamera {
+    target: Point3<f32>,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl OrbitCamera {
+    /// Looks at the middle of a box from in front and slightly above
+    pub fn looking_at(size: Vector3<f32>) -> Self {
+        OrbitCamera {
+            target: Point3::from_vec(size / 2.0),
+            yaw: 0.5,
+            pitch: 0.4,
+            distance: size.magnitude() * 1.4,
+        }
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let direction = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + direction * self.distance
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        let aspect = SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32;
+        perspective(Deg(FIELD_OF_VIEW), aspect, 0.01, 100.0)
+            * Matrix4::look_at_rh(self.eye(), self.target, Vector3::unit_y())
+    }
+
+    pub fn orbit(&mut self, dx: i32, dy: i32) {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.yaw -= dx as f32 * ORBIT_SPEED;
+        self.pitch = (self.pitch + dy as f32 * ORBIT_SPEED).clamp(-limit, limit);
+    }
+
+    pub fn zoom(&mut self, notches: i32) {
+        self.distance *= ZOOM_STEP.powi(-notches);
+    }
+}
+
+/// Runs a 3D scene for a fixed number of steps without a window
+pub fn run_headless(scene: Scene, seed: u64, steps: usize, profiler: &mut Profiler) {
+    let mut scene_data = scene.build(seed);
+    for _ in 0..steps {
+        physics_update_profiled(&mut scene_data, DELTA_TIME, profiler);
+    }
+    println!("Ran {} for {} steps, {} particles", scene.name(), steps, scene_data.particles.len());
+}
+
+/// Opens the 3D viewer, drawing particles as sprites inside an outline of the box
+pub fn run_viewer(scene: Scene, seed: u64, key_bindings: &KeyBindings, profiler: &mut Profiler) {
+    let mut scene_data = scene.build(seed);
+    let mut sdl2_data = init_sdl2();
+    let sprite_program = sprite_shader_program();
+    let mut camera = OrbitCamera::looking_at(to_f32(scene_data.size));
+    let mut overlays = Overlays::default();
+    let mut paused = false;
+    let mut time_scale: Fp = 1.0;
+    let mut pending_time: Fp = 0.0;
+
+    let mut prev_tick = sdl2_data.timer.performance_counter();
+    let tick_freq = sdl2_data.timer.performance_frequency();
+
+    unsafe {
+        gl::Viewport(0, 0, SCREEN_WIDTH as gl::types::GLsizei, SCREEN_HEIGHT as gl::types::GLsizei);
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+        gl::Enable(gl::PROGRAM_POINT_SIZE);
+    }
+
+    'main_loop: loop {
+        let mut step_requested = false;
+        for event in sdl2_data.event_pump.poll_iter() {
+            let action = match event {
+                Event::Quit { .. } => Some(Action::Quit),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => key_bindings.action(keycode),
+                Event::MouseMotion {
+                    mousestate, xrel, yrel, ..
+                } => {
+                    if mousestate.left() {
+                        camera.orbit(xrel, yrel);
+                    }
+                    None
+                }
+                Event::MouseWheel { y, .. } => {
+                    camera.zoom(y);
+                    None
+                }
+                _ => None,
+            };
+
+            // Only the actions that make sense without a cursor tool or 2D scene features
+            match action {
+                Some(Action::Quit) => break 'main_loop,
+                Some(Action::Pause) => paused = !paused,
+                Some(Action::Step) => step_requested = true,
+                Some(Action::Reset) => scene_data = scene.build(seed),
+                Some(Action::SlowDown) => {
+                    time_scale = (time_scale / TIME_SCALE_STEP).max(MIN_TIME_SCALE)
+                }
+                Some(Action::SpeedUp) => {
+                    time_scale = (time_scale * TIME_SCALE_STEP).min(MAX_TIME_SCALE)
+                }
+                Some(Action::ToggleOverlay(overlay @ (Overlay::Hud | Overlay::Profiler))) => {
+                    overlays.toggle(overlay)
+                }
+                _ => {}
+            }
+        }
+
+        let tick = sdl2_data.timer.performance_counter();
+        let true_delta_time = (tick - prev_tick) as Fp / tick_freq as Fp;
+        prev_tick = tick;
+
+        let steps = if paused {
+            pending_time = 0.0;
+            step_requested as u32
+        } else {
+            pending_time += true_delta_time * time_scale;
+            let steps = ((pending_time / DELTA_TIME) as u32).min(MAX_STEPS_PER_FRAME);
+            pending_time = (pending_time - steps as Fp * DELTA_TIME).min(DELTA_TIME);
+            steps
+        };
+        for _ in 0..steps {
+            physics_update_profiled(&mut scene_data, DELTA_TIME, profiler);
+        }
+
+        let render_start = Instant::now();
+        let sprites = particle_vertices(&scene_data);
+        let outline = box_outline_vertices(to_f32(scene_data.size));
+        let mut overlay = Vec::new();
+        if overlays.hud {
+            let text = hud_text(&scene_data, paused, time_scale);
+            push_text(&mut overlay, &text, Vector2::new(WORLD_WIDTH - 0.34, WORLD_HEIGHT - 0.02), 0.002, (1.0, 1.0, 1.0));
+        }
+        if overlays.profiler {
+            let text = format!("Stage ms: min mean max\n{}", Profiler::summary(profiler.last_window()));
+            push_text(&mut overlay, &text, Vector2::new(0.02, WORLD_HEIGHT - 0.2), 0.0015, (1.0, 1.0, 0.6));
+        }
+        profiler.record("build_vertices", render_start);
+
+        let draw_start = Instant::now();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        sprite_program.set_used();
+        sprite_program.set_mat4("ViewProjection", &camera.view_projection());
+        let focal_length = 1.0 / (FIELD_OF_VIEW.to_radians() / 2.0).tan();
+        sprite_program.set_float("PointScale", SPRITE_RADIUS * focal_length * SCREEN_HEIGHT as f32);
+        sprite_program.set_bool("Sprites", true);
+        draw(&sprites, gl::POINTS);
+        sprite_program.set_bool("Sprites", false);
+        draw(&outline, gl::LINES);
+
+        // Overlays are drawn flat on top with the 2D shader
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        sdl2_data.shader_program.set_used();
+        draw(&overlay, gl::TRIANGLES);
+
+        sdl2_data.renderer.window().gl_swap_window();
+        profiler.record("draw", draw_start);
+        profiler.tick();
+    }
+}
+
+fn to_f32(vector: Vector3<Fp>) -> Vector3<f32> {
+    vector.cast().unwrap()
+}
+
+/// Position and colour of every particle, coloured by speed like the 2D viewer
+#[allow(clippy::unnecessary_cast)] // Only unnecessary without the f64 feature
+fn particle_vertices(scene_data: &SceneData) -> Vec<f32> {
+    let particles = &scene_data.particles;
+    let mut vertices = Vec::with_capacity(6 * particles.len());
+    for i in 0..particles.len() {
+        let red = (particles.vel(i).magnitude().min(0.6) / 0.6).sqrt();
+        vertices.extend_from_slice(to_f32(particles.pos(i)).as_ref() as &[f32; 3]);
+        vertices.extend_from_slice(&[red, 0.0, 1.0 - red].map(|c: Fp| c as f32));
+    }
+    vertices
+}
+
+/// The 12 edges of the box as pairs of line vertices
+fn box_outline_vertices(size: Vector3<f32>) -> Vec<f32> {
+    let corner = |i: usize| {
+        Vector3::new(
+            if i & 1 == 0 { 0.0 } else { size.x },
+            if i & 2 == 0 { 0.0 } else { size.y },
+            if i & 4 == 0 { 0.0 } else { size.z },
+        )
+    };
+    let mut vertices = Vec::new();
+    for start in 0..8 {
+        // Corners one bit apart share an edge
+        for bit in [1, 2, 4] {
+            if start & bit == 0 {
+                for pos in [corner(start), corner(start | bit)] {
+                    vertices.extend_from_slice(&[pos.x, pos.y, pos.z, 0.6, 0.6, 0.6]);
+                }
+            }
+        }
+    }
+    vertices
+}
+
+fn hud_text(scene_data: &SceneData, paused: bool, time_scale: Fp) -> String {
+    let particles = &scene_data.particles;
+    let count = particles.len().max(1) as Fp;
+    let kinetic_energy: Fp = particles.iter().map(|p| 0.5 * p.mass * p.vel.magnitude2()).sum();
+    let max_speed = particles.iter().map(|p| p.vel.magnitude()).fold(0.0, Fp::max);
+    let mean_density = particles.density.iter().sum::<Fp>() / count;
+    format!(
+        "{}Speed: {}x\nTime: {:.2}s\nParticles: {}\nKE: {:.3}\nMax vel: {:.2}\nRho: {:.1}%",
+        if paused { "Paused\n" } else { "" },
+        time_scale,
+        scene_data.time,
+        particles.len(),
+        kinetic_energy,
+        max_speed,
+        mean_density / REST_DENSITY * 100.0,
+    )
+}
+
+fn push_text(vertices: &mut Vec<f32>, text: &str, top_left: Vector2<Fp>, pixel_size: Fp, colour: (f32, f32, f32)) {
+    for (min, max) in text_quads(text, top_left, pixel_size) {
+        let corners = [min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)];
+        for i in [0, 1, 2, 0, 2, 3] {
+            push_vertex(vertices, corners[i], colour);
+        }
+    }
+}
+
+/// Draws vertices of interleaved position and colour with the program in use
+fn draw(vertices: &[f32], mode: GLenum) {
+    if vertices.is_empty() {
+        return;
+    }
+    let stride = (6 * std::mem::size_of::<f32>()) as gl::types::GLint;
+    let (mut vbo, mut vao) = (0, 0);
+    unsafe {
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            std::mem::size_of_val(vertices) as gl::types::GLsizeiptr,
+            vertices.as_ptr() as *const gl::types::GLvoid,
+            gl::STREAM_DRAW,
+        );
+
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(
+            1,
+            3,
+            gl::FLOAT,
+            gl::FALSE,
+            stride,
+            (3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+        );
+
+        gl::DrawArrays(mode, 0, (vertices.len() / 6) as gl::types::GLsizei);
+
+        gl::BindVertexArray(0);
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::DeleteVertexArrays(1, &vao);
+        gl::DeleteBuffers(1, &vbo);
+    }
+}
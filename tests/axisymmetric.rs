@@ -0,0 +1,103 @@
+//! Axisymmetric worlds: particles right on the axis, and sampling and diagnostics over the rings
+//! particles stand for
+
+mod common;
+
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2};
+use common::run_steps;
+use fluid::config::Geometry;
+use fluid::diagnostics::Diagnostics;
+use fluid::particle::Particle;
+use fluid::physics::{compute_densities, compute_vorticity, INTERACTION_RADIUS, REST_DENSITY};
+use fluid::sampling::sample_point;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::Fp;
+
+/// Particles at `positions` in an otherwise empty axisymmetric world
+fn scene(positions: &[Vector2<Fp>]) -> SceneData {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1).with_geometry(Geometry::Axisymmetric);
+    for &pos in positions {
+        scene_data.particles.push(Particle::new(pos, 1.0 + pos.y));
+    }
+    scene_data.rebuild_neighbour_grid();
+    scene_data
+}
+
+#[test]
+fn particles_on_the_axis_have_a_density() {
+    // A lone particle on the axis, and a pair with only each other as neighbours
+    let spacing = INTERACTION_RADIUS / 2.0;
+    let mut scene_data = scene(&[
+        Vector2::new(0.0, 0.2),
+        Vector2::new(0.0, 0.6),
+        Vector2::new(0.0, 0.6 + spacing),
+    ]);
+    scene_data.particles.vel[0][1] = 0.5;
+    compute_densities(&mut scene_data);
+    compute_vorticity(&mut scene_data);
+    for (i, particle) in scene_data.particles.iter().enumerate() {
+        assert!(particle.density > 0.0 && particle.density.is_finite(), "particle {} density {}", i, particle.density);
+        assert!(particle.vorticity.is_finite(), "particle {} vorticity {}", i, particle.vorticity);
+    }
+
+    // And through whole steps, where the repulsion's pressure divides by the density too
+    run_steps(&mut scene_data, 5);
+    for (i, particle) in scene_data.particles.iter().enumerate() {
+        let finite = [particle.pos.x, particle.pos.y, particle.vel.x, particle.vel.y, particle.density, particle.pressure]
+            .iter()
+            .all(|v| v.is_finite());
+        assert!(finite, "particle {} isn't finite after stepping: {:?}", i, particle.pos);
+    }
+}
+
+#[test]
+fn sampling_matches_the_ring_densities_and_mirrors_flow_across_the_axis() {
+    // A block against the axis, all moving outwards and up
+    let spacing = 1.0 / REST_DENSITY.sqrt();
+    let positions: Vec<_> = (0..20)
+        .flat_map(|column| (0..20).map(move |row| Vector2::new(column as Fp * spacing, 0.3 + row as Fp * spacing)))
+        .collect();
+    let mut scene_data = scene(&positions);
+    let velocity = Vector2::new(0.2, 0.4);
+    for mut particle in &mut scene_data.particles {
+        particle.vel = velocity;
+    }
+    compute_densities(&mut scene_data);
+
+    for i in [0, 5, 110, 210] {
+        let sample = sample_point(&scene_data, scene_data.particles.pos(i));
+        let density = scene_data.particles.density[i];
+        assert!((sample.density - density).abs() <= density * 1e-4, "sampled {} at particle {} of {}", sample.density, i, density);
+    }
+
+    let middle = 0.3 + 10.0 * spacing;
+    let on_axis = sample_point(&scene_data, Vector2::new(0.0, middle)).velocity;
+    assert!(on_axis.x.abs() < 1e-4, "flow through the axis {:?}", on_axis);
+    assert!((on_axis.y - velocity.y).abs() < 1e-4, "flow along the axis {:?}", on_axis);
+    let off_axis = sample_point(&scene_data, Vector2::new(2.0 * INTERACTION_RADIUS, middle)).velocity;
+    assert!((off_axis - velocity).magnitude() < 1e-4, "flow away from the axis {:?}", off_axis);
+}
+
+#[test]
+fn diagnostics_are_of_whole_rings() {
+    let mut scene_data = scene(&[Vector2::new(0.1, 0.2), Vector2::new(0.3, 0.5)]);
+    scene_data.particles.vel[0] = vec![0.5, -0.5];
+    scene_data.particles.vel[1] = vec![1.0, 2.0];
+
+    // The same particles in a planar world, each carrying its whole ring's mass
+    let mut rings = SceneData::new(SpawningMethod::Random, 0, 1);
+    for particle in &scene_data.particles {
+        let mut ring = Particle::new(particle.pos, 2.0 * Fp::PI() * particle.pos.x * particle.mass);
+        ring.vel = particle.vel;
+        rings.particles.push(ring);
+    }
+
+    let (axisymmetric, planar) = (Diagnostics::compute(&scene_data), Diagnostics::compute(&rings));
+    let close = |a: Fp, b: Fp| (a - b).abs() <= b.abs() * 1e-5;
+    assert!(close(axisymmetric.kinetic_energy, planar.kinetic_energy), "{:?}\n{:?}", axisymmetric, planar);
+    assert!(close(axisymmetric.potential_energy, planar.potential_energy), "{:?}\n{:?}", axisymmetric, planar);
+    assert!(close(axisymmetric.momentum.y, planar.momentum.y), "{:?}\n{:?}", axisymmetric, planar);
+    // Opposite sides of a ring move in opposite directions
+    assert_eq!((axisymmetric.momentum.x, axisymmetric.angular_momentum), (0.0, 0.0));
+}
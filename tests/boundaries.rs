@@ -0,0 +1,134 @@
+//! World edges and kinematic walls keeping particles on the right side of them
+
+mod common;
+
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2, Zero};
+use common::run_steps;
+use fluid::boundary::{KinematicBoundary, Motion};
+use fluid::config::{BoundaryCondition, Gravity};
+use fluid::particle::Particle;
+use fluid::physics::remove_escaped_particles;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::scenes::Scene;
+use fluid::{Fp, WORLD_HEIGHT, WORLD_WIDTH};
+
+#[test]
+fn periodic_axes_wrap_within_a_moving_tank() {
+    let mut scene_data = Scene::Periodic.build(1).with_tank_motion(Motion::Oscillate {
+        amplitude: Vector2::new(0.2, 0.0),
+        period: 1.0,
+    });
+    for _ in 0..10 {
+        run_steps(&mut scene_data, 10);
+        let left = scene_data.tank_motion.state(scene_data.time).offset.x;
+        for &x in &scene_data.particles.pos[0] {
+            assert!(
+                x >= left && x <= left + WORLD_WIDTH,
+                "particle at x = {} outside the tank from {} at {}s",
+                x,
+                left,
+                scene_data.time
+            );
+        }
+    }
+}
+
+#[test]
+fn open_boundaries_remove_particles_outside_a_moving_tank() {
+    let mut scene_data = empty_scene().with_tank_motion(Motion::Oscillate {
+        amplitude: Vector2::new(0.2, 0.1),
+        period: 1.0,
+    });
+    scene_data.config.boundary_x = BoundaryCondition::Open;
+    scene_data.config.boundary_y = BoundaryCondition::Open;
+    // A quarter period in, so the tank is at its furthest up and to the right
+    scene_data.time = 0.25;
+    let inside = [
+        Vector2::new(WORLD_WIDTH + 0.1, 0.5),
+        Vector2::new(0.5, WORLD_HEIGHT + 0.05),
+        Vector2::new(0.25, 0.15),
+    ];
+    let outside = [Vector2::new(0.1, 0.5), Vector2::new(0.5, 0.05), Vector2::new(WORLD_WIDTH + 0.3, 0.5)];
+    for pos in inside.into_iter().chain(outside) {
+        scene_data.particles.push(Particle::new(pos, 1.0));
+    }
+
+    remove_escaped_particles(&mut scene_data);
+    let kept: Vec<_> = scene_data.particles.iter().map(|p| p.pos).collect();
+    assert_eq!(kept, inside);
+}
+
+/// Whether `pos` is on the anticlockwise side of the boundary's segment at `time`
+fn on_left_of(boundary: &KinematicBoundary, pos: Vector2<Fp>, time: Fp) -> bool {
+    let (start, end) = boundary.end_points(time);
+    let (along, offset) = (end - start, pos - start);
+    along.x * offset.y - along.y * offset.x > 0.0
+}
+
+/// An empty world with nothing but the particles and walls acting
+fn empty_scene() -> SceneData {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1);
+    scene_data.config.gravity = Gravity::Uniform(Vector2::zero());
+    scene_data.config.drag_coefficient = 0.0;
+    scene_data
+}
+
+#[test]
+fn fast_particles_bounce_off_an_approaching_piston() {
+    // Pointing down, so its anticlockwise side faces the particle, and moving right at up to 3 m/s
+    let piston = KinematicBoundary::new(
+        Vector2::new(0.3, 0.5),
+        0.4,
+        -Fp::FRAC_PI_2(),
+        Motion::Oscillate {
+            amplitude: Vector2::new(0.1, 0.0),
+            period: 0.2,
+        },
+    );
+    // Each covers many times the wall's thickness in a step
+    for speed in [5.0, 30.0] {
+        let mut scene_data = empty_scene().with_boundary(piston.clone());
+        let mut particle = Particle::new(Vector2::new(0.5, 0.5), 1.0);
+        particle.vel = Vector2::new(-speed, 0.0);
+        scene_data.particles.push(particle);
+        for _ in 0..40 {
+            run_steps(&mut scene_data, 1);
+            let pos = scene_data.particles.pos(0);
+            assert!(
+                on_left_of(&piston, pos, scene_data.time),
+                "particle fired at {} m/s passed through the piston to {:?} at {}s",
+                speed,
+                pos,
+                scene_data.time
+            );
+        }
+    }
+}
+
+#[test]
+fn a_fast_paddle_sweeps_particles_ahead_of_it() {
+    let half_length = 0.2;
+    let paddle = KinematicBoundary::new(
+        Vector2::new(0.5, 0.5),
+        2.0 * half_length,
+        0.0,
+        Motion::Rotate { angular_velocity: 40.0 },
+    );
+    // Resting just ahead of the paddle, whose blade moves several thicknesses a step there
+    for radius in [0.08, 0.15] {
+        let mut scene_data = empty_scene().with_boundary(paddle.clone());
+        scene_data.particles.push(Particle::new(Vector2::new(0.5 + radius, 0.52), 1.0));
+        for _ in 0..10 {
+            run_steps(&mut scene_data, 1);
+            let pos = scene_data.particles.pos(0);
+            // Unless it's been flung off the end of the blade
+            assert!(
+                on_left_of(&paddle, pos, scene_data.time) || (pos - Vector2::new(0.5, 0.5)).magnitude() > half_length,
+                "paddle passed through the particle at {:?} at {}s",
+                pos,
+                scene_data.time
+            );
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#![allow(dead_code)]
+
+use fluid::cursor_tool::CursorState;
+use fluid::math::Vector;
+use fluid::particle::Particles;
+use fluid::physics::physics_update;
+use fluid::scene_data::SceneData;
+use fluid::three_d;
+use fluid::Fp;
+
+/// Fixed time step used by the headless runs in the test suites
+pub const DELTA_TIME: Fp = 0.005;
+
+/// Runs `steps` physics steps with no cursor interaction
+pub fn run_steps(scene_data: &mut SceneData, steps: usize) {
+    for _ in 0..steps {
+        physics_update(scene_data, DELTA_TIME, &CursorState::None);
+    }
+}
+
+/// Runs `steps` physics steps of a 3D scene
+pub fn run_steps_3d(scene_data: &mut three_d::scene_data::SceneData, steps: usize) {
+    for _ in 0..steps {
+        three_d::physics::physics_update(scene_data, DELTA_TIME);
+    }
+}
+
+/// Checks the particles have come to rest. Surface particles keep jittering, so this judges by
+/// the root mean square speed of the bulk rather than the fastest particle.
+pub fn assert_settled<V: Vector>(particles: &Particles<V>) {
+    let kinetic_energy: Fp = particles.iter().map(|p| 0.5 * p.mass * p.vel.magnitude2()).sum();
+    let total_mass: Fp = particles.mass.iter().sum();
+    let rms_speed = (2.0 * kinetic_energy / total_mass).sqrt();
+    assert!(rms_speed < 0.02, "tank hasn't settled, rms speed {}", rms_speed);
+}
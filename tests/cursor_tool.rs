@@ -0,0 +1,103 @@
+//! Cursor tools pushing, painting and erasing particles
+
+use cgmath::{InnerSpace, Vector2, Zero};
+use fluid::cursor_tool::{apply_cursor_tool, CursorState, CursorTool, Falloff, Tool};
+use fluid::force_field::ForceField;
+use fluid::material::Material;
+use fluid::particle::Particle;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::Fp;
+
+const CURSOR: Vector2<Fp> = Vector2::new(0.5, 0.5);
+
+fn cursor(tool: Tool, reverse: bool) -> CursorState {
+    CursorState::Active {
+        pos: CURSOR,
+        vel: Vector2::new(1.0, -1.0),
+        reverse,
+        tool: CursorTool {
+            tool,
+            radius: 0.2,
+            strength: 10.0,
+            falloff: Falloff::Linear,
+            material: Material::Ice,
+        },
+    }
+}
+
+fn accel(state: &CursorState, pos: Vector2<Fp>) -> Vector2<Fp> {
+    let field = state.force_field().expect("tool should have a force field");
+    field.acceleration(&Particle::new(pos, 1.0), 0.0)
+}
+
+#[test]
+fn forces_fade_to_the_edge_and_reverse_flips_them() {
+    let pos = Vector2::new(0.6, 0.5);
+    // Halfway to the edge of the linear falloff, so half strength
+    let push = accel(&cursor(Tool::Push, false), pos);
+    assert!((push - Vector2::new(5.0, 0.0)).magnitude() < 1e-4, "{:?}", push);
+    assert_eq!(accel(&cursor(Tool::Pull, false), pos), -push);
+    assert_eq!(accel(&cursor(Tool::Push, true), pos), -push);
+    assert_eq!(accel(&cursor(Tool::Push, false), Vector2::new(0.75, 0.5)), Vector2::zero());
+
+    let vortex = accel(&cursor(Tool::Vortex, false), pos);
+    assert!(vortex.dot(pos - CURSOR).abs() < 1e-5, "vortex pushes out from the cursor");
+
+    // Drag brings particles up to the cursor's velocity
+    let drag = accel(&cursor(Tool::Drag, false), pos);
+    assert!((drag - Vector2::new(5.0, -5.0)).magnitude() < 1e-4, "{:?}", drag);
+
+    for falloff in [Falloff::Constant, Falloff::Linear, Falloff::Smooth] {
+        assert_eq!(falloff.weight(0.0), 1.0);
+        assert!(falloff.weight(1.0) <= falloff.weight(0.5) && falloff.weight(0.5) <= 1.0);
+    }
+    assert!(cursor(Tool::Paint, false).force_field().is_none());
+    assert!(cursor(Tool::Erase, false).force_field().is_none());
+}
+
+#[test]
+fn paint_sprays_particles_at_its_rate_within_the_radius() {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1);
+    let (steps, delta_time) = (400, 0.005);
+    for _ in 0..steps {
+        apply_cursor_tool(&mut scene_data, &cursor(Tool::Paint, false), delta_time);
+    }
+    // 20 particles a second per unit of strength, spawned at random to match fractional rates
+    let expected = 10.0 * 20.0 * steps as Fp * delta_time;
+    let count = scene_data.particles.len() as Fp;
+    assert!((count / expected - 1.0).abs() < 0.1, "painted {} particles rather than about {}", count, expected);
+    for particle in &scene_data.particles {
+        assert!((particle.pos - CURSOR).magnitude() <= 0.2);
+        assert_eq!(particle.vel, Vector2::new(1.0, -1.0));
+        assert_eq!(particle.material, Material::Ice);
+    }
+}
+
+#[test]
+fn erase_removes_the_particles_under_the_cursor() {
+    let scene = || {
+        SceneData::new(
+            SpawningMethod::Block {
+                min: Vector2::new(0.0, 0.0),
+                max: Vector2::new(1.0, 1.0),
+            },
+            400,
+            1,
+        )
+    };
+    let outside = |scene_data: &SceneData| {
+        scene_data.particles.iter().filter(|p| (p.pos - CURSOR).magnitude() >= 0.2).count()
+    };
+    // Erasing, or painting in reverse
+    for state in [cursor(Tool::Erase, false), cursor(Tool::Paint, true)] {
+        let mut scene_data = scene();
+        let kept = outside(&scene_data);
+        apply_cursor_tool(&mut scene_data, &state, 0.005);
+        assert_eq!(scene_data.particles.len(), kept);
+    }
+
+    // Tools that push don't add or remove anything
+    let mut scene_data = scene();
+    apply_cursor_tool(&mut scene_data, &cursor(Tool::Push, false), 0.005);
+    assert_eq!(scene_data.particles.len(), 400);
+}
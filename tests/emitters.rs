@@ -0,0 +1,85 @@
+//! Emitters spawning particles at their rate and drains removing them
+
+use cgmath::{InnerSpace, Vector2};
+use fluid::emitter::{apply_drains, apply_emitters, Drain, Emitter};
+use fluid::material::Material;
+use fluid::particle::Particle;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::{Fp, MAX_PARTICLE_COUNT};
+
+const DELTA_TIME: Fp = 0.005;
+
+fn tap(rate: Fp) -> Emitter {
+    Emitter::new(Vector2::new(0.5, 0.5), Vector2::new(3.0, 4.0), rate, 2.0, Material::Steam, 0.1)
+}
+
+fn empty_scene() -> SceneData {
+    SceneData::new(SpawningMethod::Random, 0, 1)
+}
+
+#[test]
+fn emitters_spawn_at_their_rate() {
+    // Rates above and below one particle a step, the slower carrying fractions between steps
+    for rate in [450.0, 150.0, 30.0] {
+        let mut scene_data = empty_scene().with_emitter(tap(rate));
+        let steps = 200;
+        for _ in 0..steps {
+            apply_emitters(&mut scene_data, DELTA_TIME);
+        }
+        let expected = rate * steps as Fp * DELTA_TIME;
+        let count = scene_data.particles.len() as Fp;
+        // Rounding in the carried fraction can leave the last particle a hair short of due
+        assert!(count <= expected && count >= expected - 1.0, "{} particles at {} a second", count, rate);
+    }
+
+    // Particles leave the nozzle along its direction, spread across its width
+    let emitter = tap(150.0);
+    let mut scene_data = empty_scene().with_emitter(emitter.clone());
+    apply_emitters(&mut scene_data, 1.0);
+    for particle in &scene_data.particles {
+        assert_eq!(particle.material, Material::Steam);
+        assert!((particle.vel - emitter.direction * emitter.speed).magnitude() < 1e-5);
+        let offset = particle.pos - emitter.pos;
+        assert!(offset.dot(emitter.direction).abs() < 1e-5, "spawned ahead of the nozzle");
+        assert!(offset.magnitude() <= emitter.jitter / 2.0, "spawned outside the nozzle");
+    }
+}
+
+#[test]
+fn emitters_stop_at_the_particle_limit_and_when_disabled() {
+    let mut scene_data = empty_scene().with_emitter(tap(1000.0));
+    apply_emitters(&mut scene_data, (MAX_PARTICLE_COUNT + 100) as Fp / 1000.0);
+    assert_eq!(scene_data.particles.len(), MAX_PARTICLE_COUNT);
+
+    let mut scene_data = empty_scene().with_emitter(tap(1000.0));
+    scene_data.toggle_emitters();
+    apply_emitters(&mut scene_data, 1.0);
+    assert!(scene_data.particles.is_empty());
+}
+
+#[test]
+fn drains_remove_the_particles_inside_them() {
+    let drain = Drain::new(Vector2::new(0.4, 0.0), Vector2::new(0.6, 0.1));
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(1.0, 0.5),
+        },
+        500,
+        1,
+    )
+    .with_drain(drain.clone());
+    let outside: Vec<Vector2<Fp>> = scene_data.particles.iter().map(|p| p.pos).filter(|&p| !drain.contains(p)).collect();
+    assert!(outside.len() < 500, "no particles start in the drain");
+
+    apply_drains(&mut scene_data);
+    let remaining: Vec<Vector2<Fp>> = scene_data.particles.iter().map(|p| p.pos).collect();
+    assert_eq!(remaining, outside, "the particles outside the drain should be kept in order");
+
+    // A disabled drain keeps everything
+    let mut scene_data = empty_scene().with_drain(drain);
+    scene_data.particles.push(Particle::new(Vector2::new(0.5, 0.05), 1.0));
+    scene_data.toggle_drains();
+    apply_drains(&mut scene_data);
+    assert_eq!(scene_data.particles.len(), 1);
+}
@@ -0,0 +1,227 @@
+//! Exports a few known particles to each text format, with a custom attribute, and reads them back
+
+use cgmath::Vector2;
+use fluid::export::{ExportFormat, ParticleExporter};
+use fluid::material::Material;
+use fluid::particle::Particle;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::Fp;
+use std::path::PathBuf;
+
+const COUNT: usize = 4;
+const TIME: Fp = 1.25;
+// Position and velocity, then the default attributes and the custom one
+const COLUMNS: [&str; 10] = [
+    "x",
+    "y",
+    "vx",
+    "vy",
+    "density",
+    "pressure",
+    "vorticity",
+    "temperature",
+    "material",
+    "kinetic_energy",
+];
+
+fn temp_dir(name: &str) -> PathBuf {
+    let precision = if cfg!(feature = "f64") { "f64" } else { "f32" };
+    std::env::temp_dir().join(format!("fluid_export_test_{}_{}_{}", precision, std::process::id(), name))
+}
+
+/// Particles whose values are all exact in binary, so they survive being written as text
+fn scene() -> SceneData {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1);
+    let materials = [Material::Water, Material::Ice, Material::Steam, Material::Water];
+    for (i, material) in materials.into_iter().enumerate() {
+        let i = i as Fp;
+        let mut particle = Particle::with_material(Vector2::new(0.25 * i, 0.5), 2.0, material);
+        particle.vel = Vector2::new(1.0, -0.5 * i);
+        particle.density = 1000.0 + i;
+        particle.pressure = 0.125 * i;
+        particle.vorticity = -i;
+        particle.temperature = 20.0 + 10.0 * i;
+        scene_data.particles.push(particle);
+    }
+    scene_data.time = TIME;
+    scene_data
+}
+
+/// The values each row should hold, in the order of `COLUMNS`
+fn expected_rows() -> Vec<[Fp; 10]> {
+    scene()
+        .particles
+        .iter()
+        .map(|p| {
+            let kinetic_energy = 0.5 * p.mass * (p.vel.x * p.vel.x + p.vel.y * p.vel.y);
+            [
+                p.pos.x,
+                p.pos.y,
+                p.vel.x,
+                p.vel.y,
+                p.density,
+                p.pressure,
+                p.vorticity,
+                p.temperature,
+                p.material as u8 as Fp,
+                kinetic_energy,
+            ]
+        })
+        .collect()
+}
+
+fn export(format: ExportFormat) -> String {
+    let dir = temp_dir(format.extension());
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("frame.{}", format.extension()));
+    ParticleExporter::new(format, &dir, 1)
+        .with_attribute("kinetic_energy", |p| {
+            0.5 * p.mass * (p.vel.x * p.vel.x + p.vel.y * p.vel.y)
+        })
+        .export_frame(&scene(), &path)
+        .unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    text
+}
+
+fn parse_values(line: &str, separator: char) -> Vec<Fp> {
+    line.split(separator).map(|v| v.trim().parse().unwrap()).collect()
+}
+
+/// The lines following the first that starts with `heading`
+fn section<'a>(lines: &[&'a str], heading: &str, count: usize) -> Vec<&'a str> {
+    let start = lines.iter().position(|l| l.starts_with(heading)).unwrap_or_else(|| panic!("no {}", heading));
+    lines[start + 1..start + 1 + count].to_vec()
+}
+
+#[test]
+fn csv_has_a_header_and_a_row_per_particle() {
+    let text = export(ExportFormat::Csv);
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines[0], COLUMNS.join(","));
+    assert_eq!(lines.len(), COUNT + 1);
+    for (line, expected) in lines[1..].iter().zip(expected_rows()) {
+        assert_eq!(parse_values(line, ','), expected);
+    }
+}
+
+#[test]
+fn legacy_vtk_counts_its_points_and_point_data() {
+    let text = export(ExportFormat::VtkLegacy);
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines[0], "# vtk DataFile Version 3.0");
+    let float_type = if cfg!(feature = "f64") { "double" } else { "float" };
+    assert!(lines.contains(&format!("POINTS {} {}", COUNT, float_type).as_str()));
+    assert!(lines.contains(&format!("VERTICES {} {}", COUNT, 2 * COUNT).as_str()));
+    assert!(lines.contains(&format!("POINT_DATA {}", COUNT).as_str()));
+
+    let expected = expected_rows();
+    for (i, line) in section(&lines, "POINTS", COUNT).into_iter().enumerate() {
+        assert_eq!(parse_values(line, ' '), [expected[i][0], expected[i][1], 0.0]);
+    }
+    for (i, line) in section(&lines, "VECTORS velocity", COUNT).into_iter().enumerate() {
+        assert_eq!(parse_values(line, ' '), [expected[i][2], expected[i][3], 0.0]);
+    }
+    for (column, name) in COLUMNS.iter().enumerate().skip(4) {
+        let heading = format!("SCALARS {} {} 1", name, float_type);
+        let values = section(&lines, &heading, COUNT + 1);
+        assert_eq!(values[0], "LOOKUP_TABLE default");
+        for (i, line) in values[1..].iter().enumerate() {
+            assert_eq!(parse_values(line, ' '), [expected[i][column]], "{} of particle {}", name, i);
+        }
+    }
+}
+
+/// Checks every tag is closed in the order it was opened, returning the names of the tags
+fn check_xml_nesting(text: &str) -> Vec<String> {
+    let (mut open, mut seen) = (Vec::new(), Vec::new());
+    let body = text.trim_start().strip_prefix(r#"<?xml version="1.0"?>"#).expect("no XML declaration");
+    for tag in body.split('<').skip(1) {
+        let tag = &tag[..tag.find('>').expect("unterminated tag")];
+        if let Some(name) = tag.strip_prefix('/') {
+            assert_eq!(open.pop().as_deref(), Some(name), "mismatched closing tag");
+        } else {
+            let name = tag.split_whitespace().next().unwrap().to_string();
+            seen.push(name.clone());
+            if !tag.ends_with('/') {
+                open.push(name);
+            }
+        }
+    }
+    assert!(open.is_empty(), "unclosed tags {:?}", open);
+    seen
+}
+
+/// The text between the opening tag containing `opening` and the next closing tag
+fn element_text<'a>(text: &'a str, opening: &str) -> &'a str {
+    let start = text.find(opening).unwrap_or_else(|| panic!("no {}", opening));
+    let start = start + text[start..].find('>').unwrap() + 1;
+    &text[start..start + text[start..].find("</").unwrap()]
+}
+
+#[test]
+fn vtp_is_well_formed_and_counts_its_points() {
+    let text = export(ExportFormat::Vtp);
+    let tags = check_xml_nesting(&text);
+    assert_eq!(tags[..3], ["VTKFile", "PolyData", "FieldData"]);
+    assert!(text.contains(&format!(r#"<Piece NumberOfPoints="{}" NumberOfVerts="{}">"#, COUNT, COUNT)));
+    assert_eq!(parse_values(element_text(&text, r#"Name="TimeValue""#), ' '), [TIME]);
+
+    let expected = expected_rows();
+    let points = element_text(&text[text.find("<Points>").unwrap()..], "<DataArray");
+    let points: Vec<_> = points.lines().filter(|l| !l.is_empty()).collect();
+    assert_eq!(points.len(), COUNT);
+    for (i, line) in points.into_iter().enumerate() {
+        assert_eq!(parse_values(line, ' '), [expected[i][0], expected[i][1], 0.0]);
+    }
+    for (column, name) in COLUMNS.iter().enumerate().skip(4) {
+        let values = parse_values(element_text(&text, &format!(r#"Name="{}""#, name)).trim(), '\n');
+        let expected: Vec<_> = expected.iter().map(|row| row[column]).collect();
+        assert_eq!(values, expected, "{}", name);
+    }
+}
+
+#[test]
+fn ply_lists_its_vertices_and_properties() {
+    let text = export(ExportFormat::Ply);
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines[..2], ["ply", "format ascii 1.0"]);
+    assert_eq!(lines[2], format!("comment time {}", TIME));
+    assert_eq!(lines[3], format!("element vertex {}", COUNT));
+
+    let end = lines.iter().position(|l| *l == "end_header").unwrap();
+    let properties: Vec<_> = lines[4..end].iter().map(|l| l.rsplit(' ').next().unwrap()).collect();
+    let mut expected_properties = vec!["x", "y", "z", "vx", "vy", "vz"];
+    expected_properties.extend(&COLUMNS[4..]);
+    assert_eq!(properties, expected_properties);
+
+    assert_eq!(lines.len(), end + 1 + COUNT);
+    for (line, row) in lines[end + 1..].iter().zip(expected_rows()) {
+        let mut expected = vec![row[0], row[1], 0.0, row[2], row[3], 0.0];
+        expected.extend(&row[4..]);
+        assert_eq!(parse_values(line, ' '), expected);
+    }
+}
+
+#[test]
+fn metadata_lists_every_array() {
+    let dir = temp_dir("metadata");
+    let scene_data = scene();
+    let exporter = ParticleExporter::new(ExportFormat::Csv, &dir, 7).with_attribute("kinetic_energy", |p| p.mass);
+    exporter.write_metadata("tap", 3, 0.005, &scene_data.config).unwrap();
+    let text = std::fs::read_to_string(dir.join("metadata.json")).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    let metadata: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(metadata["scene"], "tap");
+    assert_eq!(metadata["seed"], 3);
+    assert_eq!(metadata["interval"], 7);
+    assert_eq!(metadata["format"], "csv");
+    assert!(metadata["grid"].is_null());
+    assert!(metadata["config"].is_object());
+    let arrays: Vec<_> = metadata["arrays"].as_array().unwrap().iter().map(|a| a.as_str().unwrap()).collect();
+    let mut expected = vec!["pos", "vel"];
+    expected.extend(&COLUMNS[4..]);
+    assert_eq!(arrays, expected);
+}
@@ -0,0 +1,100 @@
+//! The built-in force fields and custom fields registered on a scene
+
+use cgmath::{InnerSpace, Vector2, Zero};
+use fluid::config::Gravity;
+use fluid::cursor_tool::CursorState;
+use fluid::force_field::{ForceField, RadialField, RegionField, TurbulenceField, UniformField, VortexField};
+use fluid::particle::Particle;
+use fluid::physics::apply_force_fields;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::Fp;
+
+/// Pushes right harder and harder as time goes on
+struct Ramp;
+
+impl ForceField for Ramp {
+    fn acceleration(&self, particle: &Particle, time: Fp) -> Vector2<Fp> {
+        Vector2::new(time * particle.mass, 0.0)
+    }
+}
+
+fn at(x: Fp, y: Fp) -> Particle {
+    Particle::new(Vector2::new(x, y), 1.0)
+}
+
+#[test]
+fn registered_fields_add_to_the_built_in_ones() {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1)
+        .with_force_field(Ramp)
+        .with_force_field(UniformField {
+            accel: Vector2::new(0.0, 1.5),
+        });
+    scene_data.config.gravity = Gravity::Uniform(Vector2::new(0.0, -1.0));
+    // In the middle of the world and at rest, so the walls and drag cancel out
+    scene_data.particles.push(at(0.5, 0.5));
+    scene_data.time = 2.0;
+
+    apply_force_fields(&mut scene_data, &CursorState::None);
+    let accel = scene_data.particles.accel(0);
+    assert!((accel - Vector2::new(2.0, 0.5)).magnitude() < 1e-4, "{:?}", accel);
+}
+
+#[test]
+fn region_fields_only_act_inside_their_rectangle() {
+    let region = RegionField {
+        min: Vector2::new(0.2, 0.2),
+        max: Vector2::new(0.4, 0.6),
+        field: Box::new(UniformField {
+            accel: Vector2::new(1.0, 2.0),
+        }),
+    };
+    assert_eq!(region.acceleration(&at(0.3, 0.5), 0.0), Vector2::new(1.0, 2.0));
+    assert_eq!(region.acceleration(&at(0.3, 0.7), 0.0), Vector2::zero());
+    assert_eq!(region.acceleration(&at(0.1, 0.5), 0.0), Vector2::zero());
+}
+
+#[test]
+fn radial_and_vortex_fields_act_within_their_radius() {
+    let centre = Vector2::new(0.5, 0.5);
+    let radial = RadialField {
+        centre,
+        strength: 3.0,
+        radius: 0.2,
+    };
+    assert!((radial.acceleration(&at(0.6, 0.5), 0.0) - Vector2::new(-3.0, 0.0)).magnitude() < 1e-5);
+    assert_eq!(radial.acceleration(&at(0.8, 0.5), 0.0), Vector2::zero());
+    assert_eq!(radial.acceleration(&at(0.5, 0.5), 0.0), Vector2::zero());
+
+    let vortex = VortexField {
+        centre,
+        strength: 2.0,
+        radius: 0.2,
+    };
+    // Anticlockwise and strongest halfway out
+    let halfway = vortex.acceleration(&at(0.6, 0.5), 0.0);
+    assert!((halfway - Vector2::new(0.0, 2.0)).magnitude() < 1e-5, "{:?}", halfway);
+    let inner = vortex.acceleration(&at(0.5, 0.55), 0.0);
+    assert!(inner.x < 0.0 && inner.magnitude() < halfway.magnitude() && inner.y.abs() < 1e-5);
+    assert_eq!(vortex.acceleration(&at(0.75, 0.5), 0.0), Vector2::zero());
+}
+
+#[test]
+fn turbulence_is_bounded_and_repeatable() {
+    let turbulence = |seed| TurbulenceField {
+        strength: 5.0,
+        scale: 0.1,
+        frequency: 1.0,
+        seed,
+    };
+    let (field, other) = (turbulence(0), turbulence(1));
+    let mut differs_over_time = false;
+    for i in 0..100 {
+        let particle = at(i as Fp * 0.013, 1.0 - i as Fp * 0.007);
+        let accel = field.acceleration(&particle, 0.3);
+        assert!(accel.x.abs() <= 5.0 && accel.y.abs() <= 5.0, "{:?}", accel);
+        assert_eq!(accel, turbulence(0).acceleration(&particle, 0.3));
+        assert_ne!(accel, other.acceleration(&particle, 0.3));
+        differs_over_time |= accel != field.acceleration(&particle, 0.8);
+    }
+    assert!(differs_over_time, "turbulence doesn't change over time");
+}
@@ -0,0 +1,136 @@
+//! Regression tests comparing short runs of every scene against recorded golden trajectories,
+//! so any change to the physics that alters behaviour shows up. After an intentional change,
+//! regenerate the goldens with
+//!
+//!     UPDATE_GOLDEN=1 cargo test --test golden
+//!
+//! and commit the files in `tests/golden` along with the change. Double precision runs differ, so
+//! they have their own goldens in `tests/golden/f64`, regenerated by also passing `--features f64`.
+
+mod common;
+
+use cgmath::InnerSpace;
+use common::run_steps;
+use fluid::scenes::Scene;
+use fluid::trajectory::{Trajectory, TrajectoryRecorder};
+use fluid::Fp;
+use std::path::PathBuf;
+
+const SEED: u64 = 1;
+const PARTICLE_LIMIT: usize = 300; // Scenes are trimmed to this to keep runs quick and goldens small
+const STEPS: usize = 100;
+const RECORD_INTERVAL: u32 = 20;
+
+// Loose enough to allow for floating point differences between platforms
+const POSITION_TOLERANCE: Fp = 1e-4;
+const VELOCITY_TOLERANCE: Fp = 1e-3;
+
+fn golden_path(scene: Scene) -> PathBuf {
+    let mut directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+    if cfg!(feature = "f64") {
+        directory.push("f64");
+    }
+    directory.join(format!("{}.fltr", scene.name()))
+}
+
+/// Runs the scene and records it to `path`
+fn record(scene: Scene, path: &PathBuf) {
+    let mut scene_data = scene.build(SEED);
+    scene_data.particles.truncate(PARTICLE_LIMIT);
+    let mut recorder = TrajectoryRecorder::create(path, scene, RECORD_INTERVAL).unwrap();
+    for _ in 0..STEPS {
+        run_steps(&mut scene_data, 1);
+        recorder.record(&scene_data).unwrap();
+    }
+    recorder.finish().unwrap();
+}
+
+fn check_scene(scene: Scene) {
+    let golden = golden_path(scene);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        record(scene, &golden);
+        return;
+    }
+
+    // Named for the precision and process, so concurrent runs don't overwrite each other's output
+    let precision = if cfg!(feature = "f64") { "f64" } else { "f32" };
+    let output = std::env::temp_dir().join(format!(
+        "fluid_golden_{}_{}_{}.fltr",
+        scene.name(),
+        precision,
+        std::process::id()
+    ));
+    record(scene, &output);
+    let expected = Trajectory::load(&golden).unwrap_or_else(|e| {
+        panic!("Failed to load golden '{}' ({}), run with UPDATE_GOLDEN=1 to create it", golden.display(), e)
+    });
+    let actual = Trajectory::load(&output).unwrap();
+    std::fs::remove_file(&output).ok();
+
+    assert_eq!(actual.frames.len(), expected.frames.len(), "{}: frame count", scene.name());
+    for (frame, (actual, expected)) in actual.frames.iter().zip(&expected.frames).enumerate() {
+        let context = format!("{} frame {} at {}s", scene.name(), frame, expected.time);
+        assert_eq!(actual.particles.len(), expected.particles.len(), "{}: particle count", context);
+        for (i, (a, e)) in actual.particles.iter().zip(&expected.particles).enumerate() {
+            assert!(
+                (a.pos - e.pos).magnitude() < POSITION_TOLERANCE,
+                "{}: particle {} at {:?}, golden {:?}",
+                context,
+                i,
+                a.pos,
+                e.pos
+            );
+            assert!(
+                (a.vel - e.vel).magnitude() < VELOCITY_TOLERANCE,
+                "{}: particle {} moving at {:?}, golden {:?}",
+                context,
+                i,
+                a.vel,
+                e.vel
+            );
+            assert!(
+                (a.mass - e.mass).abs() <= POSITION_TOLERANCE * e.mass,
+                "{}: particle {} mass {}, golden {}",
+                context,
+                i,
+                a.mass,
+                e.mass
+            );
+            assert_eq!(a.material, e.material, "{}: particle {} material", context, i);
+        }
+    }
+}
+
+#[test]
+fn golden_scenes() {
+    for scene in Scene::ALL {
+        check_scene(scene);
+    }
+}
+
+/// Every pass gathers each particle's result on its own, so a run must come out exactly the same
+/// whatever the number of threads
+#[test]
+fn thread_count_does_not_change_results() {
+    for scene in Scene::ALL {
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut scene_data = scene.build(SEED);
+                run_steps(&mut scene_data, 20);
+                scene_data.particles
+            })
+        };
+        let (serial, parallel) = (run(1), run(8));
+        for (name, a, b) in [
+            ("x", &serial.pos[0], &parallel.pos[0]),
+            ("y", &serial.pos[1], &parallel.pos[1]),
+            ("vx", &serial.vel[0], &parallel.vel[0]),
+            ("vy", &serial.vel[1], &parallel.vel[1]),
+            ("density", &serial.density, &parallel.density),
+        ] {
+            assert!(a == b, "{}: {} differs between one and eight threads", scene.name(), name);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Uniform and point source gravity, and the potential the energy diagnostics take from it
+
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2};
+use fluid::config::{Gravity, GravitySource};
+use fluid::force_field::ForceField;
+use fluid::material::Material;
+use fluid::particle::Particle;
+use fluid::physics::GRAVITY;
+use fluid::Fp;
+
+fn sources() -> Gravity {
+    Gravity::Sources(vec![
+        GravitySource {
+            pos: Vector2::new(0.5, 0.5),
+            strength: 0.15,
+            inverse_square: true,
+        },
+        GravitySource {
+            pos: Vector2::new(0.2, 0.7),
+            strength: 2.0,
+            inverse_square: false,
+        },
+    ])
+}
+
+#[test]
+fn acceleration_is_the_negative_gradient_of_the_potential() {
+    let gravities = [Gravity::Uniform(Vector2::new(0.3, GRAVITY)), sources()];
+    // Far from the sources, and inside and outside their softening radii
+    let points = [
+        Vector2::new(0.9, 0.1),
+        Vector2::new(0.52, 0.51),
+        Vector2::new(0.46, 0.5),
+        Vector2::new(0.2, 0.73),
+        Vector2::new(0.3, 0.6),
+    ];
+    let h = if cfg!(feature = "f64") { 1e-6 } else { 1e-3 };
+    for gravity in &gravities {
+        for pos in points {
+            let derivative = |step: Vector2<Fp>| {
+                (gravity.potential_at(pos + step) - gravity.potential_at(pos - step)) / (2.0 * h)
+            };
+            let gradient = Vector2::new(derivative(Vector2::new(h, 0.0)), derivative(Vector2::new(0.0, h)));
+            let accel = gravity.acceleration_at(pos);
+            assert!(
+                (accel + gradient).magnitude() < 0.01 * accel.magnitude().max(1.0),
+                "acceleration {:?} at {:?} isn't minus the potential's gradient {:?}",
+                accel,
+                pos,
+                gradient
+            );
+        }
+    }
+}
+
+#[test]
+fn point_sources_pull_towards_themselves() {
+    let gravity = Gravity::Sources(vec![GravitySource {
+        pos: Vector2::new(0.5, 0.5),
+        strength: 0.15,
+        inverse_square: true,
+    }]);
+    let near = gravity.acceleration_at(Vector2::new(0.6, 0.5));
+    let far = gravity.acceleration_at(Vector2::new(0.7, 0.5));
+    assert!(near.x < 0.0 && near.y.abs() < 1e-6, "{:?} doesn't point at the source", near);
+    // Twice the distance, a quarter of the pull
+    assert!((near.magnitude() / far.magnitude() - 4.0).abs() < 1e-3);
+}
+
+#[test]
+fn turning_uniform_gravity_keeps_its_strength() {
+    let mut gravity = Gravity::Uniform(Vector2::new(0.0, GRAVITY));
+    gravity.rotate(Fp::FRAC_PI_2());
+    let accel = gravity.acceleration_at(Vector2::new(0.0, 0.0));
+    assert!((accel - Vector2::new(-GRAVITY, 0.0)).magnitude() < 1e-5, "{:?}", accel);
+
+    gravity.point_towards(Vector2::new(3.0, 4.0));
+    let accel = gravity.acceleration_at(Vector2::new(0.0, 0.0));
+    assert!((accel - Vector2::new(0.6, 0.8) * -GRAVITY).magnitude() < 1e-5, "{:?}", accel);
+
+    // Point sources stay where they are
+    let mut gravity = sources();
+    let before = gravity.acceleration_at(Vector2::new(0.3, 0.3));
+    gravity.rotate(1.0);
+    gravity.point_towards(Vector2::new(1.0, 0.0));
+    assert_eq!(gravity.acceleration_at(Vector2::new(0.3, 0.3)), before);
+}
+
+#[test]
+fn steam_rises() {
+    let gravity = Gravity::Uniform(Vector2::new(0.0, GRAVITY));
+    let pos = Vector2::new(0.5, 0.5);
+    let water = gravity.acceleration(&Particle::with_material(pos, 1.0, Material::Water), 0.0);
+    let steam = gravity.acceleration(&Particle::with_material(pos, 1.0, Material::Steam), 0.0);
+    assert_eq!(water, Vector2::new(0.0, GRAVITY));
+    assert!(steam.y > 0.0, "steam pulled down at {:?}", steam);
+}
@@ -0,0 +1,115 @@
+//! The neighbour grid against a brute-force search over every pair, with and without periodic axes
+
+use cgmath::{InnerSpace, Vector2};
+use fluid::neighbour_grid::NeighbourGrid;
+use fluid::particle::{Particle, Particles};
+use fluid::physics::{INTERACTION_RADIUS, MAX_REPULSION_DIST};
+use fluid::{Fp, WORLD_HEIGHT, WORLD_WIDTH};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Random particles over a `size` box, with a few just inside each edge and some a little outside,
+/// which the grid clamps into its edge cells
+fn scattered_particles(size: Vector2<Fp>) -> Particles {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let mut positions: Vec<Vector2<Fp>> = (0..800)
+        .map(|_| Vector2::new(rng.gen_range(0.0..size.x), rng.gen_range(0.0..size.y)))
+        .collect();
+    for along in [0.003, 0.5, 0.997] {
+        for edge in [0.001, 0.999, -0.02, 1.02] {
+            positions.push(Vector2::new(edge * size.x, along * size.y));
+            positions.push(Vector2::new(along * size.x, edge * size.y));
+        }
+    }
+    positions.into_iter().map(|pos| Particle::new(pos, 1.0)).collect()
+}
+
+/// Checks the candidates from a grid over a `size` box around every particle, and points either
+/// side of each seam, include every particle within `range` exactly once
+fn check_against_brute_force(size: Vector2<Fp>, periodic: [bool; 2], range: Fp) {
+    let particles = scattered_particles(size);
+    let mut grid = NeighbourGrid::covering(INTERACTION_RADIUS, size);
+    grid.rebuild(&particles, &periodic);
+
+    let mut queries: Vec<Vector2<Fp>> = particles.iter().map(|p| p.pos).collect();
+    queries.extend([
+        Vector2::new(0.0, 0.0),
+        Vector2::new(size.x - 1e-4, size.y - 1e-4),
+        Vector2::new(1e-4, size.y / 2.0),
+        Vector2::new(size.x / 2.0, 1e-4),
+    ]);
+    for pos in queries {
+        let mut seen = vec![0; particles.len()];
+        grid.for_each_candidate_within(pos, range, |j| seen[j] += 1);
+        for (j, &times) in seen.iter().enumerate() {
+            assert!(times <= 1, "particle {} is a candidate {} times around {:?}", j, times, pos);
+            let distance = grid.displacement(pos, particles.pos(j)).magnitude();
+            assert!(
+                distance > range || times == 1,
+                "particle {} at {:?} is {} from {:?} but wasn't a candidate with periodic axes {:?}",
+                j,
+                particles.pos(j),
+                distance,
+                pos,
+                periodic
+            );
+        }
+    }
+}
+
+#[test]
+fn candidates_match_brute_force() {
+    let world = Vector2::new(WORLD_WIDTH, WORLD_HEIGHT);
+    for periodic in [[false, false], [true, false], [false, true], [true, true]] {
+        check_against_brute_force(world, periodic, INTERACTION_RADIUS);
+        // Repulsion reaches two cells out
+        check_against_brute_force(world, periodic, MAX_REPULSION_DIST);
+    }
+}
+
+#[test]
+fn periodic_axes_that_arent_whole_cells_across_still_find_every_neighbour() {
+    // The last column would be a sliver, so the cells are stretched to fit instead
+    for width in [1.02, 0.97, 0.5 + INTERACTION_RADIUS / 3.0] {
+        for periodic in [[true, false], [true, true]] {
+            check_against_brute_force(Vector2::new(width, 1.0), periodic, INTERACTION_RADIUS);
+            check_against_brute_force(Vector2::new(width, 1.0), periodic, MAX_REPULSION_DIST);
+        }
+    }
+}
+
+#[test]
+fn small_periodic_boxes_search_every_cell() {
+    // Too few cells across to reach two cells either way without wrapping onto the same cell
+    for cells in [1.0, 3.0, 4.0, 3.7] {
+        let size = Vector2::new(cells * INTERACTION_RADIUS, 5.0 * INTERACTION_RADIUS);
+        for periodic in [[true, false], [true, true]] {
+            check_against_brute_force(size, periodic, INTERACTION_RADIUS);
+            check_against_brute_force(size, periodic, MAX_REPULSION_DIST);
+        }
+    }
+}
+
+#[test]
+fn periodic_neighbours_interact_across_the_seam() {
+    let particles: Particles = [Vector2::new(0.01, 0.5), Vector2::new(WORLD_WIDTH - 0.01, 0.5)]
+        .into_iter()
+        .map(|pos| Particle::new(pos, 1.0))
+        .collect();
+    let mut grid = NeighbourGrid::new(INTERACTION_RADIUS);
+
+    grid.rebuild(&particles, &[true, false]);
+    let mut candidates = Vec::new();
+    grid.for_each_candidate(particles.pos(0), |j| candidates.push(j));
+    candidates.sort();
+    assert_eq!(candidates, [0, 1]);
+    let displacement = grid.displacement(particles.pos(0), particles.pos(1));
+    assert!((displacement - Vector2::new(-0.02, 0.0)).magnitude() < 1e-5, "{:?}", displacement);
+
+    // Without the wrap they're a world apart
+    grid.rebuild(&particles, &[false, false]);
+    let mut candidates = Vec::new();
+    grid.for_each_candidate(particles.pos(0), |j| candidates.push(j));
+    assert_eq!(candidates, [0]);
+    assert_eq!(grid.displacement(particles.pos(0), particles.pos(1)), particles.pos(1) - particles.pos(0));
+}
@@ -0,0 +1,134 @@
+//! Checks the hand-written `.npy` and `.npz` encoders byte for byte, and reads archives back
+//! through an independent zip reader
+
+use fluid::numpy::{write_npy, NpzWriter};
+use std::io::Read;
+use std::path::PathBuf;
+
+fn temp_path(name: &str) -> PathBuf {
+    let precision = if cfg!(feature = "f64") { "f64" } else { "f32" };
+    std::env::temp_dir().join(format!("fluid_numpy_test_{}_{}_{}.npz", precision, std::process::id(), name))
+}
+
+/// Splits an `.npy` file into its header text and data, checking the fixed framing on the way
+fn split_npy(bytes: &[u8]) -> (&str, &[u8]) {
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00", "magic and version 1.0");
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let data_start = 10 + header_len;
+    assert_eq!(data_start % 64, 0, "data should start on a 64 byte boundary");
+    let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+    assert!(header.ends_with('\n'), "header should end in a newline: {:?}", header);
+    (header, &bytes[data_start..])
+}
+
+#[test]
+fn npy_header_describes_the_array() {
+    let data: Vec<f32> = (0..6).map(|i| i as f32 * 0.5).collect();
+    let mut bytes = Vec::new();
+    write_npy(&mut bytes, &[3, 2], &data).unwrap();
+
+    let (header, body) = split_npy(&bytes);
+    assert_eq!(header.trim_end(), "{'descr': '<f4', 'fortran_order': False, 'shape': (3, 2), }");
+    let expected: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!(body, &expected[..]);
+}
+
+#[test]
+fn npy_shapes_use_python_tuple_syntax() {
+    let shape_of = |shape: &[usize], len: usize| {
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, shape, &vec![0u8; len]).unwrap();
+        let (header, body) = split_npy(&bytes);
+        assert_eq!(body.len(), len);
+        let start = header.find("'shape': ").unwrap() + "'shape': ".len();
+        header[start..header.find(", }").unwrap()].to_string()
+    };
+    assert_eq!(shape_of(&[5], 5), "(5,)");
+    assert_eq!(shape_of(&[], 1), "()");
+    assert_eq!(shape_of(&[2, 3, 2], 12), "(2, 3, 2)");
+}
+
+#[test]
+fn npy_pads_every_header_length() {
+    // Longer shapes lengthen the header text, moving where the padding has to end
+    for n in [1, 9, 99, 999, 99_999, 9_999_999] {
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, &[n, 0], &[] as &[f64]).unwrap();
+        let (header, body) = split_npy(&bytes);
+        assert!(header.contains("'descr': '<f8'"));
+        assert!(body.is_empty());
+    }
+}
+
+#[test]
+fn npz_reads_back_with_a_zip_reader() {
+    let path = temp_path("archive");
+    let positions: Vec<f64> = (0..200).map(|i| (i as f64).sin()).collect();
+    let materials: Vec<u8> = (0..100).map(|i| (i % 3) as u8).collect();
+    let mut npz = NpzWriter::create(&path).unwrap();
+    npz.add_array("pos", &[100, 2], &positions).unwrap();
+    npz.add_array("material", &[100], &materials).unwrap();
+    npz.add_array("time", &[], &[1.5f32]).unwrap();
+    npz.finish().unwrap();
+
+    let expected = [
+        ("pos.npy", encode(&[100, 2], &positions)),
+        ("material.npy", encode(&[100], &materials)),
+        ("time.npy", encode(&[], &[1.5f32])),
+    ];
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(archive.len(), expected.len());
+    for (i, (name, npy)) in expected.iter().enumerate() {
+        let mut entry = archive.by_index(i).unwrap();
+        assert_eq!(entry.name(), *name);
+        assert_eq!(entry.compression(), zip::CompressionMethod::Deflated);
+        assert_eq!(entry.size(), npy.len() as u64);
+        // Reading to the end also checks the stored CRC
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).unwrap();
+        assert!(contents == *npy, "{} doesn't match the array written", name);
+    }
+    std::fs::remove_file(&path).ok();
+}
+
+fn encode<T: fluid::numpy::NpyElement>(shape: &[usize], data: &[T]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_npy(&mut bytes, shape, data).unwrap();
+    bytes
+}
+
+#[test]
+fn npz_fails_rather_than_truncating_sizes_past_the_zip_limit() {
+    let path = temp_path("limit");
+    let is_invalid_input =
+        |result: std::io::Result<()>| result.unwrap_err().kind() == std::io::ErrorKind::InvalidInput;
+
+    // An array larger than the limit on its own
+    let mut npz = NpzWriter::create(&path).unwrap().with_size_limit(1024);
+    npz.add_array("small", &[], &[1.5f32]).unwrap();
+    assert!(is_invalid_input(npz.add_array("large", &[2000], &vec![0u8; 2000])));
+    assert!(is_invalid_input(npz.add_array(&"n".repeat(70_000), &[], &[1.5f32])));
+
+    // Arrays that fit but push the archive past the limit
+    let mut npz = NpzWriter::create(&path).unwrap().with_size_limit(1024);
+    let mut added = 0;
+    let error = loop {
+        match npz.add_array(&format!("scalar_{}", added), &[], &[added as f32]) {
+            Ok(()) => added += 1,
+            Err(error) => break error,
+        }
+        assert!(added < 100, "archive grew past its limit without failing");
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(added > 1);
+
+    // The same arrays without the one that failed still end past the limit, where the central
+    // directory would start
+    let mut npz = NpzWriter::create(&path).unwrap().with_size_limit(1024);
+    for i in 0..added {
+        npz.add_array(&format!("scalar_{}", i), &[], &[i as f32]).unwrap();
+    }
+    assert!(is_invalid_input(npz.finish()));
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,38 @@
+//! Changing particles in place through the views of the structure of arrays
+
+use cgmath::Vector2;
+use fluid::material::Material;
+use fluid::particle::{Particle, Particles};
+use fluid::Fp;
+
+fn row(count: usize) -> Particles {
+    (0..count).map(|i| Particle::new(Vector2::new(i as Fp, 0.0), 1.0)).collect()
+}
+
+#[test]
+fn changes_through_get_mut_are_written_back() {
+    let mut particles = row(3);
+    {
+        let mut particle = particles.get_mut(1);
+        particle.vel = Vector2::new(2.0, 3.0);
+        particle.material = Material::Ice;
+    }
+    assert_eq!((particles.vel[0][1], particles.vel[1][1]), (2.0, 3.0));
+    assert_eq!(particles.material[1], Material::Ice);
+    // Neighbours are untouched
+    assert_eq!((particles.vel[0][0], particles.vel[0][2]), (0.0, 0.0));
+}
+
+#[test]
+fn iter_mut_visits_every_particle_in_order() {
+    let mut particles = row(5);
+    for mut particle in &mut particles {
+        particle.pos.y = particle.pos.x * 2.0;
+        particle.temperature += 1.0;
+    }
+    assert_eq!(particles.iter_mut().len(), 5);
+    for (i, particle) in particles.iter().enumerate() {
+        assert_eq!(particle.pos, Vector2::new(i as Fp, 2.0 * i as Fp));
+        assert_eq!(particle.temperature, Particle::new(particle.pos, 1.0).temperature + 1.0);
+    }
+}
@@ -0,0 +1,164 @@
+//! Probe readings over known arrangements of particles, probe recording and the streamed CSV
+
+mod common;
+
+use cgmath::{InnerSpace, Vector2};
+use common::{run_steps, DELTA_TIME};
+use fluid::particle::Particle;
+use fluid::physics::{INTERACTION_RADIUS, REST_DENSITY};
+use fluid::probe::{record_probes, Probe, ProbeLog, ProbeShape, Quantity};
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::scenes::Scene;
+use fluid::Fp;
+
+const VELOCITY: Vector2<Fp> = Vector2::new(0.3, -0.1);
+const PRESSURE: Fp = 5.0;
+
+/// A still lattice of `columns` x `rows` unit mass particles `spacing` apart with its bottom left
+/// particle at `corner`, all moving at `VELOCITY` under `PRESSURE`
+fn lattice(corner: Vector2<Fp>, spacing: Fp, columns: usize, rows: usize) -> SceneData {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1);
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut particle = Particle::new(corner + Vector2::new(column as Fp, row as Fp) * spacing, 1.0);
+            particle.vel = VELOCITY;
+            particle.density = REST_DENSITY;
+            particle.pressure = PRESSURE;
+            scene_data.particles.push(particle);
+        }
+    }
+    scene_data.rebuild_neighbour_grid();
+    scene_data
+}
+
+/// Reads a single probe once
+fn read(scene_data: &mut SceneData, shape: ProbeShape, quantities: &[Quantity]) -> Vec<Fp> {
+    scene_data.probes = vec![Probe::new("probe", shape).with_quantities(quantities)];
+    record_probes(scene_data);
+    scene_data.probes.pop().unwrap().reading
+}
+
+#[test]
+fn scenes_without_probes_record_nothing() {
+    let mut scene_data = Scene::ALL[0].build(0);
+    scene_data.probes.clear();
+    run_steps(&mut scene_data, 5);
+    assert_eq!(scene_data.probe_time, 0.0);
+}
+
+#[test]
+fn probe_log_writes_a_row_per_step() {
+    let precision = if cfg!(feature = "f64") { "f64" } else { "f32" };
+    let path = std::env::temp_dir().join(format!("fluid_probe_log_test_{}_{}.csv", precision, std::process::id()));
+    let mut scene_data = Scene::ALL[0].build(0);
+    scene_data.probes = vec![
+        Probe::new("a", ProbeShape::Point(Vector2::new(0.5, 0.2))).with_quantities(&[Quantity::Velocity]),
+        Probe::new("b", ProbeShape::Point(Vector2::new(1.0, 0.2))).with_quantities(&[Quantity::ParticleCount]),
+    ];
+    let mut log = ProbeLog::create(&path, &scene_data).unwrap();
+    for _ in 0..3 {
+        run_steps(&mut scene_data, 1);
+        log.record(&scene_data).unwrap();
+    }
+    log.finish().unwrap();
+
+    let csv = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "time,a_vx,a_vy,b_count");
+    assert_eq!(lines.len(), 4);
+    for (step, line) in lines[1..].iter().enumerate() {
+        let values: Vec<Fp> = line.split(',').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values.len(), 4);
+        // Probes read before integration, at the time the step started
+        assert!((values[0] - step as Fp * DELTA_TIME).abs() < 1e-6, "{}", line);
+    }
+
+    // Rows have to keep matching the header
+    scene_data.probes.pop();
+    let mut log = ProbeLog::create(&path, &scene_data).unwrap();
+    scene_data.probes.push(Probe::new("c", ProbeShape::Point(Vector2::new(1.0, 0.2))));
+    run_steps(&mut scene_data, 1);
+    assert!(log.record(&scene_data).is_err());
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn water_height_gauges_read_the_depth_of_a_column() {
+    // Unit masses this far apart make up the rest density, which halves at the free surface
+    let spacing = 1.0 / REST_DENSITY.sqrt();
+    let (columns, rows) = (36, 28);
+    let depth = rows as Fp * spacing;
+    let mut scene_data = lattice(Vector2::new(0.5, 0.5) * spacing, spacing, columns, rows);
+    let middle = columns as Fp * spacing / 2.0;
+
+    // The highest particle is half a spacing below the surface
+    let point = read(&mut scene_data, ProbeShape::Point(Vector2::new(middle, 0.0)), &[Quantity::WaterHeight]);
+    assert!((point[0] - depth).abs() <= spacing, "point gauge read {} for a depth of {}", point[0], depth);
+    let raised = read(&mut scene_data, ProbeShape::Point(Vector2::new(middle, 0.1)), &[Quantity::WaterHeight]);
+    assert!((raised[0] - (point[0] - 0.1)).abs() < 1e-5, "{:?} {:?}", raised, point);
+
+    // Line gauges read to within the spacing of their samples
+    let line = ProbeShape::Line {
+        start: Vector2::new(middle, 0.0),
+        end: Vector2::new(middle, 0.5),
+    };
+    let line = read(&mut scene_data, line, &[Quantity::WaterHeight]);
+    let tolerance = INTERACTION_RADIUS / 2.0;
+    assert!((line[0] - depth).abs() <= tolerance, "line gauge read {} for a depth of {}", line[0], depth);
+
+    // Dry gauges read nothing
+    let dry = ProbeShape::Line {
+        start: Vector2::new(0.8, 0.0),
+        end: Vector2::new(0.8, 0.5),
+    };
+    assert_eq!(read(&mut scene_data, dry, &[Quantity::WaterHeight]), [0.0]);
+}
+
+#[test]
+fn probes_read_the_velocity_and_pressure_of_uniform_flow() {
+    let spacing = 1.0 / REST_DENSITY.sqrt();
+    let mut scene_data = lattice(Vector2::new(0.2, 0.2), spacing, 60, 60);
+    let centre = Vector2::new(0.2, 0.2) + Vector2::new(30.0, 30.0) * spacing;
+    let shapes = [
+        ProbeShape::Point(centre),
+        ProbeShape::Line {
+            start: centre - Vector2::new(0.1, 0.05),
+            end: centre + Vector2::new(0.1, 0.05),
+        },
+        ProbeShape::Box {
+            min: centre - Vector2::new(0.1, 0.1),
+            max: centre + Vector2::new(0.1, 0.1),
+        },
+    ];
+    for shape in shapes {
+        let reading = read(&mut scene_data, shape, &[Quantity::Velocity, Quantity::Pressure]);
+        assert!((Vector2::new(reading[0], reading[1]) - VELOCITY).magnitude() < 1e-4, "{:?}", reading);
+        assert!((reading[2] - PRESSURE).abs() < 1e-3, "{:?}", reading);
+    }
+}
+
+#[test]
+fn particle_counts_are_exact() {
+    // Particles 0.02 apart, so none sit on the edges of the box
+    let spacing = 0.02;
+    let mut scene_data = lattice(Vector2::new(0.105, 0.105), spacing, 10, 10);
+    let node = |column: Fp, row: Fp| Vector2::new(0.105, 0.105) + Vector2::new(column, row) * spacing;
+    let count = |scene_data: &mut SceneData, shape| read(scene_data, shape, &[Quantity::ParticleCount])[0];
+
+    // Five columns and rows of particles inside
+    let inside = ProbeShape::Box {
+        min: Vector2::new(0.1, 0.1),
+        max: Vector2::new(0.19, 0.19),
+    };
+    assert_eq!(count(&mut scene_data, inside), 25.0);
+    // Within two spacings either way, less the four corners of the square that are out of range
+    assert_eq!(count(&mut scene_data, ProbeShape::Point(node(5.0, 5.0))), 21.0);
+    // Rows 2 to 6 of the column and the ones either side, and the particles a spacing past each
+    // end, which is inside half a radius where the diagonals aren't
+    let line = ProbeShape::Line {
+        start: node(5.0, 2.0),
+        end: node(5.0, 6.0),
+    };
+    assert_eq!(count(&mut scene_data, line), 17.0);
+    assert_eq!(count(&mut scene_data, ProbeShape::Point(Vector2::new(0.8, 0.8))), 0.0);
+}
@@ -0,0 +1,119 @@
+//! Saving snapshots mid-run and resuming from them, in both the binary and JSON formats
+
+mod common;
+
+use common::run_steps;
+use fluid::particle::Particles;
+use fluid::scene_data::SceneData;
+use fluid::scenes::Scene;
+use fluid::snapshot::{Snapshot, SnapshotError};
+use fluid::Fp;
+use std::path::PathBuf;
+
+const SEED: u64 = 3;
+const STEPS_BEFORE: usize = 40;
+const STEPS_AFTER: usize = 40;
+
+fn temp_path(name: &str) -> PathBuf {
+    let precision = if cfg!(feature = "f64") { "f64" } else { "f32" };
+    std::env::temp_dir().join(format!("fluid_snapshot_test_{}_{}_{}", precision, std::process::id(), name))
+}
+
+/// Every column of the particles, by name
+fn columns(particles: &Particles) -> [(&'static str, &[Fp]); 10] {
+    [
+        ("x", &particles.pos[0]),
+        ("y", &particles.pos[1]),
+        ("vx", &particles.vel[0]),
+        ("vy", &particles.vel[1]),
+        ("ax", &particles.accel[0]),
+        ("ay", &particles.accel[1]),
+        ("mass", &particles.mass),
+        ("density", &particles.density),
+        ("pressure", &particles.pressure),
+        ("temperature", &particles.temperature),
+    ]
+}
+
+fn assert_identical(scene: Scene, extension: &str, resumed: &SceneData, uninterrupted: &SceneData) {
+    let context = format!("{} resumed from .{}", scene.name(), extension);
+    assert_eq!(resumed.time.to_bits(), uninterrupted.time.to_bits(), "{}: time", context);
+    assert!(resumed.rng == uninterrupted.rng, "{}: rng state", context);
+    assert_eq!(resumed.particles.len(), uninterrupted.particles.len(), "{}: particle count", context);
+    for ((name, a), (_, b)) in columns(&resumed.particles).into_iter().zip(columns(&uninterrupted.particles)) {
+        let bits = |values: &[Fp]| values.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert!(bits(a) == bits(b), "{}: {} differs", context, name);
+    }
+    assert_eq!(resumed.particles.vorticity, uninterrupted.particles.vorticity, "{}: vorticity", context);
+    assert_eq!(resumed.particles.material, uninterrupted.particles.material, "{}: material", context);
+}
+
+/// Runs `scene` on, saving a snapshot partway, then checks resuming from the snapshot plays out
+/// bit for bit the same as carrying on
+fn check_resume(scene: Scene, extension: &str) {
+    let path = temp_path(&format!("{}.{}", scene.name(), extension));
+    let mut scene_data = scene.build(SEED);
+    run_steps(&mut scene_data, STEPS_BEFORE);
+    Snapshot::capture(scene, &scene_data).save(&path).unwrap();
+    run_steps(&mut scene_data, STEPS_AFTER);
+
+    let (restored_scene, mut resumed) = Snapshot::load(&path).unwrap().restore().unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(restored_scene, scene);
+    run_steps(&mut resumed, STEPS_AFTER);
+    assert_identical(scene, extension, &resumed, &scene_data);
+}
+
+#[test]
+fn binary_snapshots_resume_exactly() {
+    for scene in Scene::ALL {
+        check_resume(scene, "bin");
+    }
+}
+
+#[test]
+fn json_snapshots_resume_exactly() {
+    for scene in Scene::ALL {
+        check_resume(scene, "json");
+    }
+}
+
+#[test]
+fn loading_rejects_files_that_arent_snapshots() {
+    let scene_data = Scene::Random.build(SEED);
+    let path = temp_path("magic.bin");
+    Snapshot::capture(Scene::Random, &scene_data).save(&path).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+
+    // The same snapshot with the other precision's magic
+    bytes[..4].copy_from_slice(if cfg!(feature = "f64") { b"FLSN" } else { b"FLSD" });
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(Snapshot::load(&path), Err(SnapshotError::WrongPrecision)));
+
+    bytes[..4].copy_from_slice(b"PK\x03\x04");
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(Snapshot::load(&path), Err(SnapshotError::NotASnapshot)));
+
+    // Too short to hold the magic
+    std::fs::write(&path, b"FL").unwrap();
+    assert!(matches!(Snapshot::load(&path), Err(SnapshotError::Io(_))));
+
+    // Cut off partway through
+    Snapshot::capture(Scene::Random, &scene_data).save(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+    assert!(matches!(Snapshot::load(&path), Err(SnapshotError::Binary(_))));
+    std::fs::remove_file(&path).ok();
+
+    let path = temp_path("not_json.json");
+    std::fs::write(&path, "{\"version\": 1}").unwrap();
+    assert!(matches!(Snapshot::load(&path), Err(SnapshotError::Text(_))));
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn restoring_rejects_unknown_scenes() {
+    let mut snapshot = Snapshot::capture(Scene::Random, &Scene::Random.build(SEED));
+    snapshot.scene = String::from("ocean");
+    assert!(matches!(snapshot.restore(), Err(SnapshotError::UnknownScene(name)) if name == "ocean"));
+}
@@ -0,0 +1,80 @@
+//! Phase changes at and around the melting, freezing, boiling and condensation thresholds
+
+use cgmath::Vector2;
+use fluid::material::{Material, BOILING_POINT, CONDENSATION_POINT, FREEZING_POINT, MELTING_POINT};
+use fluid::particle::Particle;
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::thermal::apply_phase_changes;
+use fluid::Fp;
+
+/// A scene holding a single particle of `material` at `temperature`
+fn lone_particle(material: Material, temperature: Fp) -> SceneData {
+    let mut scene_data = SceneData::new(SpawningMethod::Random, 0, 1);
+    let mut particle = Particle::with_material(Vector2::new(0.5, 0.5), 1.0, material);
+    particle.temperature = temperature;
+    scene_data.particles.push(particle);
+    scene_data
+}
+
+#[test]
+fn particles_on_a_threshold_keep_their_phase() {
+    // Each threshold with the phases either side of it
+    let thresholds = [
+        (FREEZING_POINT, [Material::Water, Material::Ice]),
+        (MELTING_POINT, [Material::Water, Material::Ice]),
+        (CONDENSATION_POINT, [Material::Water, Material::Steam]),
+        (BOILING_POINT, [Material::Water, Material::Steam]),
+    ];
+    for (temperature, materials) in thresholds {
+        for material in materials {
+            let mut scene_data = lone_particle(material, temperature);
+            for step in 0..10 {
+                apply_phase_changes(&mut scene_data);
+                assert_eq!(
+                    scene_data.particles.material[0],
+                    material,
+                    "{:?} at {} changed phase on step {}",
+                    material,
+                    temperature,
+                    step
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn phases_change_once_past_a_threshold_and_hold_inside_the_band() {
+    let cases = [
+        (Material::Water, FREEZING_POINT - 0.01, Material::Ice),
+        (Material::Water, BOILING_POINT + 0.01, Material::Steam),
+        (Material::Ice, MELTING_POINT + 0.01, Material::Water),
+        (Material::Steam, CONDENSATION_POINT - 0.01, Material::Water),
+    ];
+    for (material, temperature, expected) in cases {
+        let mut scene_data = lone_particle(material, temperature);
+        apply_phase_changes(&mut scene_data);
+        assert_eq!(scene_data.particles.material[0], expected, "{:?} at {}", material, temperature);
+        // Back between the thresholds, the new phase sticks rather than flipping back
+        let band = if temperature < 50.0 {
+            (FREEZING_POINT + MELTING_POINT) / 2.0
+        } else {
+            (CONDENSATION_POINT + BOILING_POINT) / 2.0
+        };
+        scene_data.particles.temperature[0] = band;
+        for _ in 0..10 {
+            apply_phase_changes(&mut scene_data);
+            assert_eq!(scene_data.particles.material[0], expected, "{:?} flipped back at {}", expected, band);
+        }
+    }
+}
+
+#[test]
+fn freezing_halves_the_velocity_once() {
+    let mut scene_data = lone_particle(Material::Water, FREEZING_POINT - 1.0);
+    scene_data.particles.set_vel(0, Vector2::new(2.0, -4.0));
+    for _ in 0..3 {
+        apply_phase_changes(&mut scene_data);
+    }
+    assert_eq!(scene_data.particles.vel(0), Vector2::new(1.0, -2.0));
+}
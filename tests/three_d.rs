@@ -0,0 +1,72 @@
+//! Headless checks of the 3D solver, which shares its force law with 2D but not its scenes
+
+mod common;
+
+use cgmath::Vector3;
+use common::{assert_settled, run_steps_3d};
+use fluid::physics::INTERACTION_RADIUS;
+use fluid::three_d::scene_data::{SceneData, SpawningMethod};
+use fluid::three_d::scenes::Scene;
+use fluid::Fp;
+
+/// A narrow tank small enough to settle quickly
+fn small_tank() -> SceneData {
+    let size = Vector3::new(0.4, 0.8, 0.4);
+    SceneData::new(
+        size,
+        SpawningMethod::Block {
+            min: Vector3::new(0.0, 0.0, 0.0),
+            max: Vector3::new(size.x, 0.45, size.z),
+        },
+        1600,
+        1,
+    )
+}
+
+#[test]
+fn tank_settles_under_gravity() {
+    let mut scene_data = small_tank();
+    run_steps_3d(&mut scene_data, 800);
+    let particles = &scene_data.particles;
+
+    let size = scene_data.size;
+    let inside = particles.iter().all(|p| {
+        (0..3).all(|axis| p.pos[axis] >= 0.0 && p.pos[axis] <= size[axis])
+    });
+    assert!(inside, "particles left the box");
+    assert_settled(particles);
+
+    // Compare a deep layer with a shallow one, away from the walls, floor and surface
+    let surface = particles.iter().map(|p| p.pos.y).fold(0.0, Fp::max);
+    let layer_density = |bottom: Fp, top: Fp| {
+        let densities: Vec<Fp> = particles
+            .iter()
+            .filter(|p| p.pos.y > bottom && p.pos.y < top)
+            .filter(|p| {
+                let margin = INTERACTION_RADIUS;
+                [p.pos.x, p.pos.z].iter().all(|&c| c > margin && c < size.x - margin)
+            })
+            .map(|p| p.density)
+            .collect();
+        assert!(!densities.is_empty(), "no particles between heights {} and {}", bottom, top);
+        densities.iter().sum::<Fp>() / densities.len() as Fp
+    };
+    let middle = surface / 2.0;
+    let deep = layer_density(INTERACTION_RADIUS, middle);
+    let shallow = layer_density(middle, surface - INTERACTION_RADIUS);
+    assert!(deep > shallow, "density should rise with depth, deep {} shallow {}", deep, shallow);
+}
+
+#[test]
+fn scenes_build_inside_their_boxes() {
+    for scene in Scene::ALL {
+        assert_eq!(Scene::from_name(scene.name()), Some(scene));
+        let scene_data = scene.build(0);
+        assert!(!scene_data.particles.is_empty(), "{} has no particles", scene.name());
+        let size = scene_data.size;
+        let inside = scene_data.particles.iter().all(|p| {
+            (0..3).all(|axis| p.pos[axis] >= 0.0 && p.pos[axis] <= size[axis])
+        });
+        assert!(inside, "{} spawns particles outside its box", scene.name());
+    }
+}
@@ -0,0 +1,133 @@
+//! Recording trajectories and loading them back, including runs cut short
+
+mod common;
+
+use common::run_steps;
+use fluid::scenes::Scene;
+use fluid::trajectory::{Frame, Trajectory, TrajectoryError, TrajectoryRecorder};
+use std::path::PathBuf;
+
+const STEPS: usize = 23;
+const RECORD_INTERVAL: u32 = 5;
+
+fn temp_path(name: &str) -> PathBuf {
+    let precision = if cfg!(feature = "f64") { "f64" } else { "f32" };
+    std::env::temp_dir().join(format!("fluid_trajectory_test_{}_{}_{}.fltr", precision, std::process::id(), name))
+}
+
+/// Records a run of `scene`, returning the frames it should hold
+fn record(path: &PathBuf, scene: Scene) -> Vec<Frame> {
+    let mut scene_data = scene.build(1);
+    let mut recorder = TrajectoryRecorder::create(path, scene, RECORD_INTERVAL).unwrap();
+    let mut frames = Vec::new();
+    for step in 0..STEPS {
+        run_steps(&mut scene_data, 1);
+        recorder.record(&scene_data).unwrap();
+        if step % RECORD_INTERVAL as usize == 0 {
+            frames.push(Frame::capture(&scene_data));
+        }
+    }
+    recorder.finish().unwrap();
+    frames
+}
+
+fn assert_frames_match(loaded: &[Frame], expected: &[Frame]) {
+    for (i, (loaded, expected)) in loaded.iter().zip(expected).enumerate() {
+        assert_eq!(loaded.time, expected.time, "frame {} time", i);
+        assert_eq!(loaded.particles.len(), expected.particles.len(), "frame {} particle count", i);
+        for (a, e) in loaded.particles.iter().zip(&expected.particles) {
+            let same = a.pos == e.pos && a.vel == e.vel && a.mass == e.mass && a.material == e.material;
+            assert!(same, "frame {} differs", i);
+        }
+    }
+}
+
+#[test]
+fn recorded_frames_load_back() {
+    let path = temp_path("round_trip");
+    // The tap gains particles as it goes
+    let expected = record(&path, Scene::Tap);
+    let trajectory = Trajectory::load(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(trajectory.scene().unwrap(), Scene::Tap);
+    assert_eq!(trajectory.header.record_interval, RECORD_INTERVAL);
+    assert_eq!(trajectory.frames.len(), STEPS.div_ceil(RECORD_INTERVAL as usize));
+    assert_frames_match(&trajectory.frames, &expected);
+    assert!(trajectory.frames.last().unwrap().particles.len() > trajectory.frames[0].particles.len());
+
+    assert_eq!(trajectory.start_time(), expected[0].time);
+    assert_eq!(trajectory.end_time(), expected.last().unwrap().time);
+    assert_eq!(trajectory.frame_index_at(0.0), 0);
+    assert_eq!(trajectory.frame_index_at(expected[2].time), 2);
+    assert_eq!(trajectory.frame_index_at((expected[2].time + expected[3].time) / 2.0), 2);
+    assert_eq!(trajectory.frame_index_at(100.0), expected.len() - 1);
+}
+
+#[test]
+fn runs_cut_short_load_up_to_their_last_complete_frame() {
+    let path = temp_path("truncated");
+    let expected = record(&path, Scene::Tap);
+    let bytes = std::fs::read(&path).unwrap();
+
+    let mut previous_count = expected.len();
+    for fraction in [0.95, 0.8, 0.6, 0.4] {
+        let length = (bytes.len() as f64 * fraction) as usize;
+        std::fs::write(&path, &bytes[..length]).unwrap();
+        let trajectory = Trajectory::load(&path).unwrap_or_else(|e| panic!("cut to {} bytes: {}", length, e));
+        assert!(trajectory.frames.len() < expected.len(), "cut to {} bytes but every frame loaded", length);
+        assert!(trajectory.frames.len() <= previous_count);
+        assert_frames_match(&trajectory.frames, &expected);
+        previous_count = trajectory.frames.len();
+    }
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn corrupted_runs_fail_to_load() {
+    let path = temp_path("corrupted");
+    // Enough particles that frames are still being decompressed after the header has been read
+    record(&path, Scene::Random);
+    let bytes = std::fs::read(&path).unwrap();
+
+    // Damage within the compressed stream, unlike a run cut short, is an error rather than an end
+    for fraction in [0.1, 0.15, 0.3, 0.5, 0.7, 0.9] {
+        let mut bytes = bytes.clone();
+        let at = (bytes.len() as f64 * fraction) as usize;
+        bytes[at] ^= 0x5a;
+        bytes[at + 1] ^= 0xa5;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Trajectory::load(&path).is_err(), "corrupted at byte {} but loaded", at);
+    }
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn loading_rejects_files_that_arent_trajectories() {
+    let path = temp_path("magic");
+    record(&path, Scene::Tap);
+    let mut bytes = std::fs::read(&path).unwrap();
+
+    bytes[..4].copy_from_slice(if cfg!(feature = "f64") { b"FLTR" } else { b"FLTD" });
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(Trajectory::load(&path), Err(TrajectoryError::WrongPrecision)));
+
+    bytes[..4].copy_from_slice(b"\x1f\x8b\x08\x00");
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(Trajectory::load(&path), Err(TrajectoryError::NotATrajectory)));
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn replayed_frames_keep_each_rings_mass() {
+    // Rings in an axisymmetric world carry mass per unit length, which differs from ring to ring
+    let mut scene_data = Scene::Jet.build(1);
+    run_steps(&mut scene_data, 20);
+    let frame = Frame::capture(&scene_data);
+
+    let mut replayed = Scene::Jet.build(1);
+    frame.apply_to(&mut replayed);
+    assert_eq!(replayed.time, scene_data.time);
+    assert_eq!(replayed.particles.mass, scene_data.particles.mass);
+    assert!(scene_data.particles.mass.iter().any(|&m| m != scene_data.particles.mass[0]));
+}
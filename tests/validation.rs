@@ -0,0 +1,398 @@
+//! Canonical fluid benchmarks run headless against the solver. The solver uses pairwise
+//! repulsion with linear drag rather than full SPH pressure and viscosity, so each case checks
+//! what it should reproduce and the tolerances reflect how close it currently gets. Poiseuille
+//! channel flow isn't covered, it needs viscosity and no-slip walls the solver doesn't have, so
+//! the channel is only checked against drag-limited plug flow. The suites pass in both
+//! precisions, run `cargo test --features f64` for double precision.
+
+mod common;
+
+use cgmath::num_traits::FloatConst;
+use cgmath::{InnerSpace, Vector2};
+use common::{assert_settled, run_steps, DELTA_TIME};
+use fluid::boundary::{Keyframe, KinematicBoundary, Motion};
+use fluid::config::{BoundaryCondition, Geometry, Gravity, SimulationConfig};
+use fluid::diagnostics::Diagnostics;
+use fluid::physics::{GRAVITY, INTERACTION_RADIUS};
+use fluid::sampling::{GridSampler, GridSpec};
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::Fp;
+
+/// Martin & Moyce (1952) dam break onto a dry bed with a square column of width `a`:
+/// front position Z = x / a against T = t * sqrt(2g / a)
+const MARTIN_MOYCE: [(Fp, Fp); 13] = [
+    (0.41, 1.11),
+    (0.84, 1.22),
+    (1.19, 1.44),
+    (1.43, 1.67),
+    (1.63, 1.89),
+    (1.83, 2.11),
+    (1.98, 2.33),
+    (2.20, 2.56),
+    (2.32, 2.78),
+    (2.51, 3.00),
+    (2.65, 3.22),
+    (2.83, 3.44),
+    (2.98, 3.67),
+];
+
+const CHANNEL_FORCE: Fp = 1.0; // Acceleration driving the channel flow
+
+/// A static vertical wall at `x`, used to make tanks narrower than the world
+fn vertical_wall(x: Fp, motion: Motion) -> KinematicBoundary {
+    KinematicBoundary::new(Vector2::new(x, 0.5), 1.0, Fp::FRAC_PI_2(), motion)
+}
+
+/// Slope and coefficient of determination of a least squares line through `points`
+fn linear_fit(points: &[(Fp, Fp)]) -> (Fp, Fp) {
+    let n = points.len() as Fp;
+    let mean_x = points.iter().map(|p| p.0).sum::<Fp>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<Fp>() / n;
+    let sxy: Fp = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let sxx: Fp = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let syy: Fp = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+    (sxy / sxx, sxy * sxy / (sxx * syy))
+}
+
+fn percentile(mut values: Vec<Fp>, fraction: Fp) -> Fp {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[((values.len() - 1) as Fp * fraction) as usize]
+}
+
+/// A settled column should come to rest with density rising linearly with depth and pressure
+/// holding up the fluid above
+#[test]
+fn hydrostatic_tank() {
+    let width = 0.25;
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(width, 0.4),
+        },
+        1200,
+        1,
+    )
+    .with_boundary(vertical_wall(width + 0.01, Motion::Static));
+    run_steps(&mut scene_data, 800);
+
+    assert_settled(&scene_data.particles);
+    assert!(scene_data.particles.iter().all(|p| p.pos.x < width + 0.01), "particles leaked through the wall");
+    // A few particles still rattling fast can hide in the RMS, it comes out about 0.05
+    let speeds = scene_data.particles.iter().map(|p| p.vel.magnitude()).collect();
+    let fast = percentile(speeds, 0.99);
+    assert!(fast < 0.08, "99th percentile speed {} in a settled tank", fast);
+
+    // Average over a column away from the walls
+    let surface = scene_data.particles.iter().map(|p| p.pos.y).fold(0.0, Fp::max);
+    let spec = GridSpec {
+        min: Vector2::new(0.05, 0.0),
+        max: Vector2::new(width - 0.05, surface),
+        columns: 4,
+        rows: 16,
+    };
+    let fields = GridSampler::new(spec).sample(&scene_data);
+    let row_mean = |values: &[Fp], row| {
+        (0..spec.columns).map(|c| values[fields.index(c, row)]).sum::<Fp>() / spec.columns as Fp
+    };
+
+    // Skip the layers within a kernel of the floor and the surface, where the kernel is truncated
+    let profile: Vec<(Fp, Fp)> = (0..spec.rows)
+        .map(|row| (surface - spec.point(0, row).y, row_mean(&fields.density, row)))
+        .filter(|&(depth, _)| depth > INTERACTION_RADIUS / 2.0 && depth < surface - INTERACTION_RADIUS)
+        .collect();
+    let (slope, r_squared) = linear_fit(&profile);
+    assert!(slope > 0.0, "density should rise with depth, slope {}", slope);
+    assert!(r_squared > 0.97, "density profile isn't linear in depth, r^2 {}", r_squared);
+
+    let bottom = row_mean(&fields.pressure, 1);
+    let middle = row_mean(&fields.pressure, spec.rows / 2);
+    let top = row_mean(&fields.pressure, spec.rows - 1);
+    assert!(bottom > middle && middle >= top, "pressure {} {} {} should fall with height", bottom, middle, top);
+
+    // Below the surface layer the pressure from the repulsion should carry the weight of the
+    // fluid above, which rises with the density integrated up to the surface
+    let row_height = spec.cell_size().y;
+    let mut weight_above = 0.0;
+    let mut load = Vec::new();
+    for row in (0..spec.rows).rev() {
+        let half_row_weight = row_mean(&fields.density, row) * -GRAVITY * row_height / 2.0;
+        weight_above += half_row_weight;
+        let depth = surface - spec.point(0, row).y;
+        if depth > INTERACTION_RADIUS && depth < surface - INTERACTION_RADIUS {
+            load.push((weight_above, row_mean(&fields.pressure, row)));
+        }
+        weight_above += half_row_weight;
+    }
+    let (slope, r_squared) = linear_fit(&load);
+    // The slope comes out about 0.89, the deeper layers carry a little less than their share
+    assert!(
+        (slope - 1.0).abs() < 0.2 && r_squared > 0.97,
+        "pressure should rise with the weight above, slope {} r^2 {}",
+        slope,
+        r_squared
+    );
+}
+
+/// An axisymmetric tank is a cylinder of water, which should settle just like the planar one
+/// without piling up or thinning out along the axis
+#[test]
+fn axisymmetric_tank_settles() {
+    let radius = 0.3;
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(radius, 0.4),
+        },
+        1200,
+        1,
+    )
+    .with_geometry(Geometry::Axisymmetric)
+    .with_boundary(vertical_wall(radius + 0.01, Motion::Static));
+    run_steps(&mut scene_data, 1200);
+
+    assert_settled(&scene_data.particles);
+    assert!(scene_data.particles.pos[0].iter().all(|&x| x >= 0.0), "particles crossed the axis");
+
+    // Compare the strip along the axis with the rest of the bulk, away from the floor and surface
+    let surface = scene_data.particles.iter().map(|p| p.pos.y).fold(0.0, Fp::max);
+    let mean_density = |min_x: Fp, max_x: Fp| {
+        let densities: Vec<Fp> = scene_data
+            .particles
+            .iter()
+            .filter(|p| p.pos.x >= min_x && p.pos.x < max_x)
+            .filter(|p| p.pos.y > INTERACTION_RADIUS && p.pos.y < surface - INTERACTION_RADIUS)
+            .map(|p| p.density)
+            .collect();
+        densities.iter().sum::<Fp>() / densities.len() as Fp
+    };
+    let axis = mean_density(0.0, INTERACTION_RADIUS);
+    let bulk = mean_density(INTERACTION_RADIUS, radius - INTERACTION_RADIUS);
+    assert!(
+        (axis / bulk - 1.0).abs() < 0.1,
+        "density along the axis {} is far from the bulk {}",
+        axis,
+        bulk
+    );
+}
+
+/// A column of water released across the floor of a tank twice as wide keeps its volume. A
+/// cylinder spread to twice its radius falls to a quarter of its depth, where a planar column
+/// only halves, so the axisymmetric pool should come out about half as deep relative to its
+/// column as the planar one.
+#[test]
+fn axisymmetric_column_spreads_thinner_than_planar() {
+    let (column, tank) = (0.2, 0.4);
+    let release_time = 1.0;
+    let depth_fraction = |geometry| {
+        let gate = vertical_wall(
+            column + 0.01,
+            Motion::Keyframes(vec![
+                Keyframe {
+                    time: release_time,
+                    offset: Vector2::new(0.0, 0.0),
+                    angle: 0.0,
+                },
+                Keyframe {
+                    time: release_time + 0.02,
+                    offset: Vector2::new(0.0, 1.0),
+                    angle: 0.0,
+                },
+            ]),
+        );
+        let mut scene_data = SceneData::new(
+            SpawningMethod::Block {
+                min: Vector2::new(0.0, 0.0),
+                max: Vector2::new(column, 0.4),
+            },
+            1200,
+            1,
+        )
+        .with_geometry(geometry)
+        .with_boundary(gate)
+        .with_boundary(vertical_wall(tank + 0.01, Motion::Static));
+        // Surfaces away from the axis and the walls, where the repulsion thins the fluid out
+        let surface = |scene_data: &SceneData, max_x: Fp| {
+            let heights = scene_data
+                .particles
+                .iter()
+                .filter(|p| p.pos.x > INTERACTION_RADIUS && p.pos.x < max_x - INTERACTION_RADIUS)
+                .map(|p| p.pos.y)
+                .collect();
+            percentile(heights, 0.95)
+        };
+        run_steps(&mut scene_data, (release_time / DELTA_TIME) as usize - 5);
+        let column_surface = surface(&scene_data, column);
+        run_steps(&mut scene_data, 600);
+        surface(&scene_data, tank) / column_surface
+    };
+
+    let planar = depth_fraction(Geometry::Planar);
+    let axisymmetric = depth_fraction(Geometry::Axisymmetric);
+    // Each pool is less compressed than its column so comes out deeper than water would, the
+    // shallower axisymmetric one a little more so, which puts the ratio nearer 0.6
+    let ratio = axisymmetric / planar;
+    assert!(
+        (ratio - column / tank).abs() < 0.12,
+        "the axisymmetric pool is {} of its column's depth and the planar one {}, a ratio of {}",
+        axisymmetric,
+        planar,
+        ratio
+    );
+}
+
+/// A square column is settled behind a gate, which is then lifted out of the way. The front
+/// runs slightly ahead of the experiment early on as the column is more compressible than water.
+#[test]
+fn dam_break_front() {
+    let a = 0.2;
+    let release_time = 1.0;
+    let gate = vertical_wall(
+        a + 0.01,
+        Motion::Keyframes(vec![
+            Keyframe {
+                time: release_time,
+                offset: Vector2::new(0.0, 0.0),
+                angle: 0.0,
+            },
+            Keyframe {
+                time: release_time + 0.02,
+                offset: Vector2::new(0.0, 1.0),
+                angle: 0.0,
+            },
+        ]),
+    );
+    // Twice as tall as it is wide before it settles to about square
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(a, 2.0 * a),
+        },
+        600,
+        1,
+    )
+    .with_boundary(gate);
+    run_steps(&mut scene_data, (release_time / DELTA_TIME).round() as usize);
+    let height = scene_data.particles.iter().map(|p| p.pos.y).fold(0.0, Fp::max);
+    assert!((height / a - 1.0).abs() < 0.2, "column settled to {} rather than about {}", height, a);
+
+    // The experiment is inviscid enough that drag would only slow the front down
+    scene_data.config.drag_coefficient = 0.0;
+    let time_scale = (2.0 * -GRAVITY / a).sqrt();
+    for (t, expected) in MARTIN_MOYCE {
+        let target = release_time + t / time_scale;
+        let steps = ((target - scene_data.time) / DELTA_TIME).round() as usize;
+        run_steps(&mut scene_data, steps);
+        // Ignore the odd particle splashing ahead of the front
+        let front = percentile(scene_data.particles.iter().map(|p| p.pos.x).collect(), 0.99) / a;
+        assert!(
+            (front - expected).abs() / expected < 0.2,
+            "front at Z = {} at T = {}, experiment {}",
+            front,
+            t,
+            expected
+        );
+    }
+}
+
+/// A body force along a periodic channel, run for `steps` steps. With no viscosity, nothing
+/// transfers shear from the walls, so the whole depth speeds up together towards the
+/// drag-limited velocity rather than forming a parabola.
+fn channel_flow(steps: usize) -> SceneData {
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(1.0, 0.15),
+        },
+        600,
+        1,
+    );
+    scene_data.config.boundary_x = BoundaryCondition::Periodic;
+    scene_data.config.gravity = Gravity::Uniform(Vector2::new(CHANNEL_FORCE, GRAVITY));
+    run_steps(&mut scene_data, steps);
+    scene_data
+}
+
+#[test]
+fn channel_flow_reaches_drag_limited_velocity() {
+    let drag = SimulationConfig::default().drag_coefficient;
+    let terminal = CHANNEL_FORCE / drag;
+    for steps in [50, 100, 200, 400] {
+        let scene_data = channel_flow(steps);
+
+        // Particle forces cancel in pairs and the channel has no side walls, so the mean velocity
+        // follows the Euler steps of dv/dt = F - cv exactly
+        let total_mass: Fp = scene_data.particles.mass.iter().sum();
+        let mean = Diagnostics::compute(&scene_data).momentum.x / total_mass;
+        let expected = terminal * (1.0 - (1.0 - drag * DELTA_TIME).powi(steps as i32));
+        assert!(
+            (mean - expected).abs() / expected < 0.01,
+            "mean velocity {} rather than {} after {} steps",
+            mean,
+            expected,
+            steps
+        );
+    }
+
+    // Mean velocity in horizontal bands through the depth of the flow, which is a plug
+    let scene_data = channel_flow(400);
+    let surface = scene_data.particles.iter().map(|p| p.pos.y).fold(0.0, Fp::max);
+    let mut bands = [(0.0, 0); 5];
+    for particle in &scene_data.particles {
+        let band = ((particle.pos.y / surface * bands.len() as Fp) as usize).min(bands.len() - 1);
+        bands[band].0 += particle.vel.x;
+        bands[band].1 += 1;
+    }
+    for (total, count) in bands {
+        let velocity = total / count.max(1) as Fp;
+        assert!((velocity - terminal).abs() / terminal < 0.05, "velocity {} rather than {}", velocity, terminal);
+    }
+}
+
+/// A periodic Taylor–Green vortex with no gravity. Without viscosity, only drag decays it, which
+/// takes kinetic energy down as exp(-2ct). Particle collisions add a little more dissipation.
+#[test]
+fn taylor_green_vortex_decay() {
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(1.0, 1.0),
+        },
+        1024,
+        1,
+    );
+    scene_data.config.boundary_x = BoundaryCondition::Periodic;
+    scene_data.config.boundary_y = BoundaryCondition::Periodic;
+    scene_data.config.gravity = Gravity::Uniform(Vector2::new(0.0, 0.0));
+
+    let k = 2.0 * Fp::PI();
+    let vortex = |pos: Vector2<Fp>| {
+        Vector2::new((k * pos.x).sin() * (k * pos.y).cos(), -(k * pos.x).cos() * (k * pos.y).sin())
+    };
+    for mut particle in &mut scene_data.particles {
+        particle.vel = vortex(particle.pos) * 0.2;
+    }
+    let initial = Diagnostics::compute(&scene_data);
+
+    run_steps(&mut scene_data, 100);
+    let diagnostics = Diagnostics::compute(&scene_data);
+    let decay_rate = -(diagnostics.kinetic_energy / initial.kinetic_energy).ln() / scene_data.time;
+    let expected_rate = 2.0 * scene_data.config.drag_coefficient;
+    assert!(
+        decay_rate >= expected_rate && decay_rate < 1.25 * expected_rate,
+        "energy decay rate {} rather than about {}",
+        decay_rate,
+        expected_rate
+    );
+    assert!(diagnostics.angular_momentum.abs() < 1e-3, "vortex gained spin: {:?}", diagnostics);
+
+    // The flow should still be the same vortex pattern, just weaker
+    let (mut dot, mut speed_sq, mut pattern_sq) = (0.0, 0.0, 0.0);
+    for particle in &scene_data.particles {
+        let pattern = vortex(particle.pos);
+        dot += particle.vel.dot(pattern);
+        speed_sq += particle.vel.magnitude2();
+        pattern_sq += pattern.magnitude2();
+    }
+    let correlation = dot / (speed_sq * pattern_sq).sqrt();
+    assert!(correlation > 0.95, "vortex lost its shape, correlation {}", correlation);
+}
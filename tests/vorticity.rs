@@ -0,0 +1,94 @@
+//! Per-particle vorticity and the confinement force that feeds it back into the flow
+
+use cgmath::{InnerSpace, Vector2, Zero};
+use fluid::config::Gravity;
+use fluid::physics::{apply_vorticity_confinement, compute_densities, compute_vorticity, INTERACTION_RADIUS};
+use fluid::scene_data::{SceneData, SpawningMethod};
+use fluid::Fp;
+
+const CENTRE: Vector2<Fp> = Vector2::new(0.5, 0.5);
+
+/// A block of fluid filling the middle of the world, with every particle moving at `velocity` of
+/// its offset from the centre, and its densities and vorticity worked out
+fn swirling_block(velocity: impl Fn(Vector2<Fp>) -> Vector2<Fp>) -> SceneData {
+    let mut scene_data = SceneData::new(
+        SpawningMethod::Block {
+            min: Vector2::new(0.2, 0.2),
+            max: Vector2::new(0.8, 0.8),
+        },
+        1600,
+        1,
+    );
+    scene_data.config.gravity = Gravity::Uniform(Vector2::zero());
+    for mut particle in &mut scene_data.particles {
+        particle.vel = velocity(particle.pos - CENTRE);
+    }
+    scene_data.rebuild_neighbour_grid();
+    compute_densities(&mut scene_data);
+    compute_vorticity(&mut scene_data);
+    scene_data
+}
+
+/// Indices of the particles more than a kernel inside the block, where the sums aren't truncated
+fn interior(scene_data: &SceneData) -> Vec<usize> {
+    (0..scene_data.particles.len())
+        .filter(|&i| {
+            let offset = scene_data.particles.pos(i) - CENTRE;
+            offset.x.abs().max(offset.y.abs()) < 0.3 - INTERACTION_RADIUS
+        })
+        .collect()
+}
+
+#[test]
+fn rigid_rotation_has_twice_its_angular_velocity() {
+    let omega = 3.0;
+    let scene_data = swirling_block(|offset| Vector2::new(-offset.y, offset.x) * omega);
+    for i in interior(&scene_data) {
+        let vorticity = scene_data.particles.vorticity[i];
+        assert!(
+            (vorticity / (2.0 * omega) - 1.0).abs() < 0.05,
+            "vorticity {} at {:?} rather than {}",
+            vorticity,
+            scene_data.particles.pos(i),
+            2.0 * omega
+        );
+    }
+
+    // A uniform flow doesn't rotate
+    let scene_data = swirling_block(|_| Vector2::new(1.0, -2.0));
+    for i in interior(&scene_data) {
+        assert!(scene_data.particles.vorticity[i].abs() < 1e-3, "uniform flow has vorticity");
+    }
+}
+
+#[test]
+fn confinement_spins_up_a_vortex() {
+    // Lamb–Oseen vortex, whose vorticity peaks at the centre and falls away with the core radius
+    let core = 0.1;
+    let mut scene_data = swirling_block(|offset| {
+        let r2 = offset.magnitude2().max(1e-6);
+        Vector2::new(-offset.y, offset.x) * ((1.0 - (-r2 / (core * core)).exp()) / r2)
+    });
+
+    // With confinement off the accelerations are left alone
+    scene_data.config.vorticity_epsilon = 0.0;
+    apply_vorticity_confinement(&mut scene_data);
+    assert!(scene_data.particles.accel[0].iter().chain(&scene_data.particles.accel[1]).all(|&a| a == 0.0));
+
+    // Otherwise particles are pushed along the swirl, not towards or away from its centre
+    scene_data.config.vorticity_epsilon = 0.05;
+    apply_vorticity_confinement(&mut scene_data);
+    let (mut along, mut across) = (0.0, 0.0);
+    for i in interior(&scene_data) {
+        let offset = scene_data.particles.pos(i) - CENTRE;
+        if offset.magnitude() < INTERACTION_RADIUS {
+            continue;
+        }
+        let swirl = Vector2::new(-offset.y, offset.x).normalize();
+        let accel = scene_data.particles.accel(i);
+        along += accel.dot(swirl);
+        across += accel.dot(offset.normalize()).abs();
+    }
+    assert!(along > 0.0, "confinement slowed the vortex, {}", along);
+    assert!(across < 0.1 * along, "confinement pushes across the swirl, {} against {}", across, along);
+}